@@ -0,0 +1,180 @@
+use std::{
+    fs::OpenOptions,
+    io::{
+        self,
+        Write,
+    },
+    thread::{
+        self,
+        JoinHandle,
+    },
+};
+
+use flume::Sender;
+use neogrok_protocol::protocol::types::Rights;
+use serde::{
+    Serialize,
+    Serializer,
+};
+
+use crate::{
+    config::{
+        audit::AuditCfg,
+        permissions::PermissionGroup,
+    },
+    user::Peer,
+    utils::unix_timestamp_millis,
+};
+
+const RIGHT_NAMES: [(Rights, &str); 6] = [
+    (Rights::CAN_CREATE_TCP, "create_tcp"),
+    (Rights::CAN_SELECT_TCP, "select_tcp"),
+    (Rights::CAN_CREATE_UDP, "create_udp"),
+    (Rights::CAN_SELECT_UDP, "select_udp"),
+    (Rights::CAN_CREATE_HTTP, "create_http"),
+    (Rights::CAN_SELECT_HTTP, "select_http"),
+];
+
+/// Security-relevant event. Secrets (such as the magic) are
+/// never included.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    AuthSucceeded {
+        #[serde(serialize_with = "serialize_rights")]
+        rights: Rights,
+    },
+    AuthFailed,
+
+    /// Rights were changed by the config reload
+    RightsChanged {
+        #[serde(serialize_with = "serialize_rights")]
+        rights: Rights,
+    },
+
+    /// User lacks the `right` to create the tunnel
+    PermissionDenied {
+        #[serde(serialize_with = "serialize_rights")]
+        right: Rights,
+        requested_port: u16,
+    },
+
+    TunnelCreated {
+        protocol: &'static str,
+        port: u16,
+
+        /// Zero if the port was chosen by the server
+        requested_port: u16,
+    },
+    TunnelClosed {
+        port: u16,
+    },
+}
+
+/// One line of the audit log
+#[derive(Serialize)]
+struct Record<'a> {
+    /// Unix time in milliseconds
+    timestamp: u64,
+
+    session: u64,
+    address: String,
+    group: &'static str,
+
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+/// Append-only JSON lines sink for the [`AuditEvent`]s.
+/// Records are written by the dedicated thread, so the slow
+/// disk doesn't stall sessions.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    lines: Option<Sender<Vec<u8>>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl AuditLog {
+    pub fn record(
+        &self,
+        peer: &Peer,
+        group: PermissionGroup,
+        event: AuditEvent,
+    ) {
+        let Some(lines) = &self.lines else {
+            return;
+        };
+
+        let record = Record {
+            timestamp: unix_timestamp_millis(),
+            session: peer.session,
+            address: peer.address.to_string(),
+            group: group.name(),
+            event: &event,
+        };
+        let mut line = serde_json::to_vec(&record)
+            .expect("Audit record is always serializable");
+        line.push(b'\n');
+
+        if lines.send(line).is_err() {
+            tracing::error!(
+                ?event,
+                "audit writer is stopped, record is lost"
+            );
+        }
+    }
+
+    pub fn open(cfg: &AuditCfg) -> io::Result<Self> {
+        let Some(path) = &cfg.file else {
+            return Ok(Self::default());
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let (lines, rx) = flume::unbounded::<Vec<u8>>();
+        let writer = thread::Builder::new()
+            .name("neogrok-audit".to_owned())
+            .spawn(move || {
+                // Whole line is written at once, so records are
+                // never interleaved
+                for line in rx {
+                    if let Err(error) = file.write_all(&line) {
+                        tracing::error!(
+                            %error,
+                            record = %String::from_utf8_lossy(&line),
+                            "failed to write audit record"
+                        );
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            lines: Some(lines),
+            writer: Some(writer),
+        })
+    }
+}
+
+impl Drop for AuditLog {
+    /// Waits until the queued records are written
+    fn drop(&mut self) {
+        self.lines.take();
+        if let Some(writer) = self.writer.take() {
+            writer.join().unwrap_or_default();
+        }
+    }
+}
+
+fn serialize_rights<S: Serializer>(
+    rights: &Rights,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(
+        RIGHT_NAMES
+            .iter()
+            .filter(|(right, _)| rights.contains(*right))
+            .map(|(_, name)| name),
+    )
+}
@@ -1,19 +1,32 @@
+use bytes::Bytes;
+use idpool::prelude::GenerationalId;
+use neogrok_protocol::{
+    hisui::frame::ConnectMetadata,
+    protocol::error::ProtocolError,
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ShutdownToken;
 
 #[derive(Debug)]
 pub enum MasterCommand {
     Disconnected {
-        id: u16,
+        id: GenerationalId<u16>,
     },
     Connected {
-        id: u16,
+        id: GenerationalId<u16>,
         tx: flume::Sender<SlaveCommand>,
+        metadata: ConnectMetadata,
     },
 
     Forward {
-        id: u16,
-        buffer: Vec<u8>,
+        id: GenerationalId<u16>,
+        buffer: Bytes,
+    },
+
+    /// Error that should be reported to the server creator
+    Error {
+        error: ProtocolError,
     },
 
     Closed,
@@ -21,6 +34,6 @@ pub enum MasterCommand {
 
 #[derive(Debug)]
 pub enum SlaveCommand {
-    Forward { buffer: Vec<u8> },
+    Forward { buffer: Bytes },
     ForceDisconnect,
 }
@@ -0,0 +1,14 @@
+use ipnet::IpNet;
+use serde::Deserialize;
+
+/// Server-wide access rules, applied to every created
+/// server in addition to the rules specified by the server
+/// creator
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AccessCfg {
+    #[serde(default)]
+    pub allow: Vec<IpNet>,
+
+    #[serde(default)]
+    pub deny: Vec<IpNet>,
+}
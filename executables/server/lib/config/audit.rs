@@ -0,0 +1,10 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct AuditCfg {
+    /// Append-only JSON lines file, audit is disabled if
+    /// not specified
+    pub file: Option<PathBuf>,
+}
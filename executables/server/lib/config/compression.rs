@@ -1,18 +1,69 @@
+use std::{
+    fs,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
 use integral_enum::IntegralEnum;
-use neogrok_protocol::{
-    compression::algorithms::polymorphic::{
-        BufCompressor,
-        BufDecompressor,
+use neogrok_protocol::compression::{
+    adaptive::AdaptivePolicy,
+    algorithms::{
+        polymorphic::{
+            BufCompressor,
+            BufDecompressor,
+        },
+        registry::{
+            self,
+            AlgorithmId,
+            CompressionRegistry,
+        },
+        zstd::dictionary::ZStdDictionary,
     },
-    protocol::types::CompressionAlgorithm,
+    offload::CompressionPool,
+    types::CompressionStrategy,
 };
 use serde::Deserialize;
 
+use super::error::ConfigLoadError;
+
 #[derive(Deserialize, IntegralEnum)]
 #[serde(rename_all = "snake_case")]
 pub enum CfgCompressionAlgorithm {
     Deflate = 0,
     ZStd = 1,
+    Lz4 = 2,
+    Brotli = 3,
+}
+
+const fn default_min_ratio() -> f64 {
+    1.1
+}
+
+const fn default_skip_frames() -> u32 {
+    16
+}
+
+const fn default_max_skip_frames() -> u32 {
+    1024
+}
+
+/// Pauses compression of the clients whose traffic doesn't
+/// shrink, e.g. TLS or already compressed files
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct AdaptiveCfg {
+    /// `before / after` below which compression is paused
+    #[serde(default = "default_min_ratio")]
+    pub min_ratio: f64,
+
+    /// Frames forwarded without compression before the next
+    /// probe, doubled after every failed probe
+    #[serde(default = "default_skip_frames")]
+    pub skip_frames: u32,
+
+    #[serde(default = "default_max_skip_frames")]
+    pub max_skip_frames: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -20,32 +71,162 @@ pub struct CompressionData {
     pub level: u8,
     pub algorithm: CfgCompressionAlgorithm,
     pub threshold: u16,
+
+    /// Offer the `STREAM_COMPRESSION` capability, only zstd
+    /// supports it. Costs 2-4MiB per session.
+    #[serde(default)]
+    pub streaming: bool,
+
+    /// zstd dictionary offered to the users, trained by the
+    /// `train-dict` command
+    #[serde(default)]
+    pub dictionary: Option<PathBuf>,
+
+    /// Compress every payload above the threshold if
+    /// missing
+    #[serde(default)]
+    pub adaptive: Option<AdaptiveCfg>,
+
+    /// Prepared `dictionary`, set when the config is loaded
+    #[serde(skip)]
+    pub loaded_dictionary: Option<Arc<ZStdDictionary>>,
+}
+
+fn default_offload_threads() -> NonZeroUsize {
+    thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+}
+
+const fn default_offload_min_size() -> usize {
+    512
+}
+
+/// Moves (de)compression of the large payloads off the
+/// runtime workers, so high levels don't stall other
+/// sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct OffloadCfg {
+    /// Payloads (de)compressed at once across all sessions,
+    /// defaults to the number of CPUs
+    #[serde(default = "default_offload_threads")]
+    pub threads: NonZeroUsize,
+
+    /// Shorter payloads are processed in place
+    #[serde(default = "default_offload_min_size")]
+    pub min_size: usize,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CompressionCfg {
     pub default: CompressionData,
+
+    #[serde(default)]
+    pub offload: Option<OffloadCfg>,
+}
+
+impl OffloadCfg {
+    pub fn to_pool(self) -> CompressionPool {
+        CompressionPool::new(self.threads, self.min_size)
+    }
 }
 
 impl CfgCompressionAlgorithm {
-    pub fn to_protocol(self) -> CompressionAlgorithm {
+    pub fn id(self) -> AlgorithmId {
         match self {
-            Self::Deflate => CompressionAlgorithm::Deflate,
-            Self::ZStd => CompressionAlgorithm::ZStd,
+            Self::Deflate => registry::DEFLATE,
+            Self::ZStd => registry::ZSTD,
+            Self::Lz4 => registry::LZ4,
+            Self::Brotli => registry::BROTLI,
         }
     }
 }
 
 impl CompressionData {
     pub fn to_pair(&self) -> (BufCompressor, BufDecompressor) {
-        match self.algorithm {
-            CfgCompressionAlgorithm::Deflate => (
-                BufCompressor::deflate(self.level),
-                BufDecompressor::deflate(),
+        CompressionRegistry::builtin()
+            .pair(self.algorithm.id(), self.level)
+            .expect("Failed to create compressor")
+    }
+
+    pub fn strategy(&self) -> CompressionStrategy {
+        let with_threshold = self.threshold;
+        match self.adaptive {
+            Some(cfg) => CompressionStrategy::Adaptive {
+                with_threshold,
+                policy: AdaptivePolicy {
+                    min_ratio: cfg.min_ratio,
+                    skip_frames: cfg.skip_frames,
+                    max_skip_frames: cfg.max_skip_frames,
+                },
+            },
+            None => CompressionStrategy::TryCompress { with_threshold },
+        }
+    }
+
+    pub fn supports_streaming(&self) -> bool {
+        self.streaming && self.algorithm == CfgCompressionAlgorithm::ZStd
+    }
+
+    /// Dictionary which can be negotiated, only zstd
+    /// supports it
+    pub fn dictionary(&self) -> Option<&Arc<ZStdDictionary>> {
+        self.loaded_dictionary
+            .as_ref()
+            .filter(|_| self.algorithm == CfgCompressionAlgorithm::ZStd)
+    }
+
+    /// Pair used after the `STREAM_COMPRESSION` capability
+    /// or the dictionary was negotiated
+    pub fn to_session_pair(
+        &self,
+        streaming: bool,
+        dictionary: bool,
+    ) -> (BufCompressor, BufDecompressor) {
+        match (streaming, dictionary.then(|| self.dictionary()).flatten())
+        {
+            (true, Some(dictionary)) => (
+                BufCompressor::zstd_stream_with_dictionary(
+                    self.level, dictionary,
+                ),
+                BufDecompressor::zstd_stream_with_dictionary(Arc::clone(
+                    dictionary,
+                )),
+            ),
+            (true, None) => (
+                BufCompressor::zstd_stream(self.level),
+                BufDecompressor::zstd_stream(),
             ),
-            CfgCompressionAlgorithm::ZStd => {
-                (BufCompressor::zstd(self.level), BufDecompressor::zstd())
-            }
+            (false, Some(dictionary)) => (
+                BufCompressor::zstd_with_dictionary(Arc::clone(
+                    dictionary,
+                )),
+                BufDecompressor::zstd_with_dictionary(Arc::clone(
+                    dictionary,
+                )),
+            ),
+            (false, None) => self.to_pair(),
         }
     }
+
+    /// Reads and prepares the `dictionary`, if any
+    pub fn load_dictionary(&mut self) -> Result<(), ConfigLoadError> {
+        let Some(path) = &self.dictionary else {
+            return Ok(());
+        };
+        let error = |reason: String| ConfigLoadError::Dictionary {
+            path: path.clone(),
+            reason,
+        };
+
+        let raw = fs::read(path).map_err(|e| error(e.to_string()))?;
+        let dictionary = ZStdDictionary::try_new(raw, self.level)
+            .map_err(|e| error(e.to_string()))?;
+        tracing::info!(
+            path = %path.display(),
+            id = dictionary.id(),
+            "loaded compression dictionary"
+        );
+        self.loaded_dictionary = Some(Arc::new(dictionary));
+
+        Ok(())
+    }
 }
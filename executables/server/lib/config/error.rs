@@ -1,4 +1,7 @@
-use std::io;
+use std::{
+    io,
+    path::PathBuf,
+};
 
 use thiserror::Error;
 
@@ -9,6 +12,18 @@ pub enum ConfigLoadError {
 
     #[error("toml load filed: {0}")]
     Format(toml::de::Error),
+
+    #[error("invalid {variable} environment variable: {reason}")]
+    Env {
+        variable: &'static str,
+        reason: String,
+    },
+
+    #[error("failed to load dictionary {}: {reason}", path.display())]
+    Dictionary { path: PathBuf, reason: String },
+
+    #[error("config has {errors} error(s), see the log above")]
+    Invalid { errors: usize },
 }
 
 impl From<toml::de::Error> for ConfigLoadError {
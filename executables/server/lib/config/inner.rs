@@ -3,27 +3,97 @@ use std::{
     path::Path,
 };
 
+use neogrok_protocol::hisui::writer::FlushPolicy;
 use serde::Deserialize;
 
 use super::{
+    access::AccessCfg,
+    audit::AuditCfg,
     compression::CompressionCfg,
     error::ConfigLoadError,
+    logging::LoggingCfg,
+    metrics::MetricsCfg,
     permissions::PermissionsCfg,
+    quotas::QuotasCfg,
+    shaping::ShapingCfg,
+    timeouts::TimeoutsCfg,
 };
 
+const fn default_drain_timeout() -> u16 {
+    30
+}
+
+const fn default_coalesce_bytes() -> usize {
+    16 * 1024
+}
+
+const fn default_coalesce_frames() -> usize {
+    64
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct TcpBufferCfg {
     pub per_client: usize,
     pub read: usize,
 }
 
+/// Frames written to the user are batched into one write
+/// until any of the limits is reached or there is nothing
+/// left to send
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CoalesceCfg {
+    #[serde(default = "default_coalesce_bytes")]
+    pub max_bytes: usize,
+
+    #[serde(default = "default_coalesce_frames")]
+    pub max_frames: usize,
+}
+
+impl Default for CoalesceCfg {
+    fn default() -> Self {
+        Self {
+            max_bytes: default_coalesce_bytes(),
+            max_frames: default_coalesce_frames(),
+        }
+    }
+}
+
+impl CoalesceCfg {
+    pub const fn policy(&self) -> FlushPolicy {
+        FlushPolicy {
+            max_bytes: self.max_bytes,
+            max_frames: self.max_frames,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ServerCfg {
     pub listen: String,
     pub buffer: TcpBufferCfg,
 
+    /// Every frame is written separately if missing
+    pub coalesce: Option<CoalesceCfg>,
+
     pub magic: String,
     pub name: String,
+
+    /// Seconds given to the active connections to finish
+    /// after the shutdown signal
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout: u16,
+
+    /// Check config file for modifications every
+    /// `watch_interval` seconds and reload it. Disabled
+    /// by default, SIGHUP reloads config regardless of
+    /// this option.
+    pub watch_interval: Option<u64>,
+
+    /// Start even with the publicly known magic or with the
+    /// base users allowed to select ports, these are
+    /// reported as warnings then
+    #[serde(default)]
+    pub allow_insecure: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -38,6 +108,27 @@ pub struct Config {
 
     pub compression: CompressionCfg,
     pub permissions: PermissionsCfg,
+
+    #[serde(default)]
+    pub access: AccessCfg,
+
+    #[serde(default)]
+    pub quotas: QuotasCfg,
+
+    #[serde(default)]
+    pub shaping: ShapingCfg,
+
+    #[serde(default)]
+    pub timeouts: TimeoutsCfg,
+
+    #[serde(default)]
+    pub logging: LoggingCfg,
+
+    #[serde(default)]
+    pub audit: AuditCfg,
+
+    #[serde(default)]
+    pub metrics: MetricsCfg,
 }
 
 impl Config {
@@ -0,0 +1,74 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+};
+
+use serde::Deserialize;
+
+fn default_level() -> String {
+    "info".to_owned()
+}
+
+fn default_file_prefix() -> String {
+    "neogrokd.log".to_owned()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+
+    /// One JSON object per line, span fields are included
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LogFileCfg {
+    pub directory: PathBuf,
+
+    /// File name, rotated files get the date suffix
+    #[serde(default = "default_file_prefix")]
+    pub prefix: String,
+
+    #[serde(default)]
+    pub rotation: LogRotation,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LoggingCfg {
+    /// Default level for all modules
+    #[serde(default = "default_level")]
+    pub level: String,
+
+    /// Per-module levels, e.g. `"neogrok::proxy" = "warn"`
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Write logs to the file instead of stdout
+    pub file: Option<LogFileCfg>,
+}
+
+impl Default for LoggingCfg {
+    fn default() -> Self {
+        Self {
+            level: default_level(),
+            filters: BTreeMap::new(),
+            format: LogFormat::default(),
+            file: None,
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MetricsCfg {
+    /// Address of the Prometheus metrics endpoint, disabled
+    /// if not specified
+    pub listen: Option<String>,
+}
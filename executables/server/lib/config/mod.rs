@@ -1,7 +1,19 @@
+pub mod access;
+pub mod audit;
 pub mod compression;
 pub mod error;
+pub mod logging;
+pub mod metrics;
+pub mod overrides;
 pub mod permissions;
+pub mod quotas;
+pub mod shaping;
+pub mod timeouts;
+pub mod validate;
 
 mod inner;
 
 pub use inner::*;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,159 @@
+use std::{
+    env::{
+        self,
+        VarError,
+    },
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use super::{
+    error::ConfigLoadError,
+    validate::{
+        self,
+        Severity,
+    },
+    CoalesceCfg,
+    Config,
+};
+
+/// Overrides specified through the command line, take
+/// precedence over the environment variables
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub listen: Option<String>,
+}
+
+/// Where config came from, used to load it again on reload
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub path: PathBuf,
+    pub overrides: Overrides,
+}
+
+impl ConfigSource {
+    /// Loads config file, applies environment and command
+    /// line overrides on top of it and validates the
+    /// result. Every found issue is logged. Compression
+    /// dictionary is loaded last.
+    pub fn load(&self) -> Result<Config, ConfigLoadError> {
+        let mut config = Config::try_load_from(&self.path)?;
+        config.apply_env_overrides()?;
+        self.overrides.apply(&mut config);
+
+        let issues = config.validate();
+        validate::report(&issues);
+
+        let errors = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+        if errors != 0 {
+            return Err(ConfigLoadError::Invalid { errors });
+        }
+        config.compression.default.load_dictionary()?;
+
+        Ok(config)
+    }
+
+    pub fn new(path: PathBuf, overrides: Overrides) -> Self {
+        Self { path, overrides }
+    }
+}
+
+impl Overrides {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(listen) = &self.listen {
+            config.server.listen = listen.clone();
+        }
+    }
+}
+
+impl Config {
+    /// Overrides `server` and `runtime` fields with the
+    /// `NEOGROK_<SECTION>_<FIELD>` environment variables
+    pub fn apply_env_overrides(&mut self) -> Result<(), ConfigLoadError> {
+        let server = &mut self.server;
+
+        env_override("NEOGROK_SERVER_LISTEN", &mut server.listen)?;
+        env_override("NEOGROK_SERVER_MAGIC", &mut server.magic)?;
+        env_override("NEOGROK_SERVER_NAME", &mut server.name)?;
+        env_override(
+            "NEOGROK_SERVER_BUFFER_READ",
+            &mut server.buffer.read,
+        )?;
+        env_override(
+            "NEOGROK_SERVER_BUFFER_PER_CLIENT",
+            &mut server.buffer.per_client,
+        )?;
+        env_override(
+            "NEOGROK_SERVER_DRAIN_TIMEOUT",
+            &mut server.drain_timeout,
+        )?;
+        if let Some(interval) = env_value("NEOGROK_SERVER_WATCH_INTERVAL")?
+        {
+            server.watch_interval = Some(interval);
+        }
+        env_override(
+            "NEOGROK_SERVER_ALLOW_INSECURE",
+            &mut server.allow_insecure,
+        )?;
+
+        // Either of them enables coalescing
+        let max_bytes = env_value("NEOGROK_SERVER_COALESCE_MAX_BYTES")?;
+        let max_frames = env_value("NEOGROK_SERVER_COALESCE_MAX_FRAMES")?;
+        if max_bytes.is_some() || max_frames.is_some() {
+            let coalesce = server
+                .coalesce
+                .get_or_insert_with(CoalesceCfg::default);
+            coalesce.max_bytes = max_bytes.unwrap_or(coalesce.max_bytes);
+            coalesce.max_frames =
+                max_frames.unwrap_or(coalesce.max_frames);
+        }
+
+        env_override("NEOGROK_RUNTIME_WORKERS", &mut self.runtime.workers)
+    }
+}
+
+fn env_override<T>(
+    variable: &'static str,
+    target: &mut T,
+) -> Result<(), ConfigLoadError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = env_value(variable)? {
+        *target = value;
+    }
+
+    Ok(())
+}
+
+fn env_value<T>(
+    variable: &'static str,
+) -> Result<Option<T>, ConfigLoadError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = match env::var(variable) {
+        Ok(value) => value,
+        Err(VarError::NotPresent) => return Ok(None),
+        Err(VarError::NotUnicode(_)) => {
+            return Err(ConfigLoadError::Env {
+                variable,
+                reason: "value is not valid unicode".to_owned(),
+            })
+        }
+    };
+
+    value
+        .parse()
+        .map(Some)
+        .map_err(|error: T::Err| ConfigLoadError::Env {
+            variable,
+            reason: error.to_string(),
+        })
+}
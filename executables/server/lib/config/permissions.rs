@@ -1,6 +1,13 @@
+use integral_enum::IntegralEnum;
 use neogrok_protocol::protocol::types::Rights;
 use serde::Deserialize;
 
+#[derive(IntegralEnum)]
+pub enum PermissionGroup {
+    Base,
+    Magic,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 pub struct ProtocolEntry {
     pub tcp: bool,
@@ -8,6 +15,14 @@ pub struct ProtocolEntry {
     pub http: bool,
 }
 
+impl ProtocolEntry {
+    pub const NONE: Self = Self {
+        tcp: false,
+        udp: false,
+        http: false,
+    };
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PermissionCan {
     pub create: ProtocolEntry,
@@ -25,6 +40,25 @@ pub struct PermissionsCfg {
     pub magic: PermissionsEntry,
 }
 
+impl PermissionGroup {
+    /// Name of the group's config section
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Base => "base",
+            Self::Magic => "magic",
+        }
+    }
+}
+
+impl PermissionsCfg {
+    pub fn group(&self, group: PermissionGroup) -> &PermissionsEntry {
+        match group {
+            PermissionGroup::Base => &self.base,
+            PermissionGroup::Magic => &self.magic,
+        }
+    }
+}
+
 impl PermissionCan {
     pub fn to_protocol_rights(&self) -> Rights {
         let mut flags = Rights::empty();
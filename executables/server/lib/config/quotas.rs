@@ -0,0 +1,36 @@
+use serde::Deserialize;
+
+const fn default_traffic_period() -> u64 {
+    // 30 days
+    30 * 24 * 60 * 60
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct TrafficQuotaCfg {
+    /// Maximum amount of bytes transferred in both
+    /// directions during the period
+    pub limit: u64,
+
+    /// Period length in seconds
+    #[serde(default = "default_traffic_period")]
+    pub period: u64,
+}
+
+/// Limits applied to every user of the permission group
+/// separately, missing limit means no limit at all.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct QuotaEntry {
+    pub max_tunnels: Option<usize>,
+    pub max_clients: Option<usize>,
+
+    pub traffic: Option<TrafficQuotaCfg>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct QuotasCfg {
+    #[serde(default)]
+    pub base: QuotaEntry,
+
+    #[serde(default)]
+    pub magic: QuotaEntry,
+}
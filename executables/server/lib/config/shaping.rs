@@ -0,0 +1,37 @@
+use std::num::NonZeroU64;
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct RateCfg {
+    /// Bytes per second in both directions
+    pub rate: NonZeroU64,
+
+    /// Maximum amount of bytes that can be sent at once,
+    /// defaults to the one second of traffic
+    pub burst: Option<NonZeroU64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct GroupShapingCfg {
+    /// Shared by all users of the permission group
+    pub group: Option<RateCfg>,
+
+    /// Shared by all tunnels of the single user
+    pub user: Option<RateCfg>,
+
+    pub tunnel: Option<RateCfg>,
+    pub client: Option<RateCfg>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ShapingCfg {
+    /// Shared by all tunnels on this server
+    pub server: Option<RateCfg>,
+
+    #[serde(default)]
+    pub base: GroupShapingCfg,
+
+    #[serde(default)]
+    pub magic: GroupShapingCfg,
+}
@@ -0,0 +1,188 @@
+use std::env;
+
+use crate::config::{
+    error::ConfigLoadError,
+    quotas::TrafficQuotaCfg,
+    validate::Severity,
+    CoalesceCfg,
+    Config,
+};
+
+/// Config shipped with the server
+const SHIPPED: &str = include_str!("../../../../neogrok.toml");
+
+fn shipped() -> Config {
+    toml::from_str(SHIPPED).unwrap()
+}
+
+fn issues(config: &Config) -> Vec<(Severity, String)> {
+    config
+        .validate()
+        .into_iter()
+        .map(|issue| (issue.severity, issue.key))
+        .collect()
+}
+
+fn error(key: &str) -> (Severity, String) {
+    (Severity::Error, key.to_owned())
+}
+
+fn warning(key: &str) -> (Severity, String) {
+    (Severity::Warning, key.to_owned())
+}
+
+#[test]
+fn test_shipped_config() {
+    let mut config = shipped();
+    assert_eq!(issues(&config), [error("server.magic")]);
+
+    config.server.allow_insecure = true;
+    assert_eq!(issues(&config), [warning("server.magic")]);
+}
+
+#[test]
+fn test_validate_insecure_permissions() {
+    let mut config = shipped();
+    config.server.magic = "secret".to_owned();
+    config.permissions.base.can.select.tcp = true;
+    assert_eq!(issues(&config), [error("permissions.base.can.select")]);
+
+    config.server.allow_insecure = true;
+    assert_eq!(issues(&config), [warning("permissions.base.can.select")]);
+}
+
+#[test]
+fn test_validate_server() {
+    let mut config = shipped();
+    config.server.listen = "0.0.0.0".to_owned();
+    config.server.magic.clear();
+    config.server.buffer.read = 0;
+    config.server.coalesce = Some(CoalesceCfg {
+        max_bytes: 0,
+        ..Default::default()
+    });
+    config.server.watch_interval = Some(0);
+
+    assert_eq!(
+        issues(&config),
+        [
+            error("server.listen"),
+            error("server.magic"),
+            error("server.buffer.read"),
+            error("server.coalesce.max_bytes"),
+            error("server.watch_interval"),
+        ]
+    );
+
+    config.server.buffer.read = u16::MAX as usize + 1;
+    assert!(issues(&config).contains(&error("server.buffer.read")));
+}
+
+#[test]
+fn test_validate_compression() {
+    let mut config = shipped();
+    config.server.magic = "secret".to_owned();
+    config.compression.default.level = 13;
+    config.compression.default.threshold = 1024;
+    config.compression.default.streaming = true;
+    // Forwarded payloads are limited by the per-client buffer
+    config.server.buffer.read = 4096;
+
+    assert_eq!(
+        issues(&config),
+        [
+            error("compression.default.level"),
+            warning("compression.default.threshold"),
+            warning("compression.default.streaming"),
+        ]
+    );
+
+    config.compression.default.level = 10;
+    config.compression.default.streaming = false;
+    config.server.buffer.read = 512;
+    config.server.buffer.per_client = 4096;
+    assert_eq!(issues(&config), []);
+}
+
+#[test]
+fn test_validate_quotas_and_timeouts() {
+    let mut config = shipped();
+    config.server.magic = "secret".to_owned();
+    config.quotas.magic.traffic = Some(TrafficQuotaCfg {
+        limit: 0,
+        period: 0,
+    });
+    config.timeouts.session_idle = Some(30);
+    config.timeouts.keepalive_misses = 0;
+
+    assert_eq!(
+        issues(&config),
+        [
+            error("quotas.magic.traffic.period"),
+            warning("quotas.magic.traffic.limit"),
+            error("timeouts.keepalive_misses"),
+            warning("timeouts.session_idle"),
+        ]
+    );
+}
+
+#[test]
+fn test_validate_logging() {
+    let mut config = shipped();
+    config.server.magic = "secret".to_owned();
+    config.logging.level = "loud".to_owned();
+    config
+        .logging
+        .filters
+        .insert("neogrok::hisui".to_owned(), "verbose".to_owned());
+    config
+        .logging
+        .filters
+        .insert("neogrok=proxy".to_owned(), "warn".to_owned());
+
+    assert_eq!(
+        issues(&config),
+        [
+            error("logging.level"),
+            error("logging.filters.\"neogrok::hisui\""),
+            error("logging.filters.\"neogrok=proxy\""),
+        ]
+    );
+}
+
+// The only test touching the environment, tests run in
+// parallel
+#[test]
+fn test_env_overrides() {
+    let mut config = shipped();
+    env::set_var("NEOGROK_SERVER_LISTEN", "127.0.0.1:7000");
+    env::set_var("NEOGROK_SERVER_COALESCE_MAX_FRAMES", "8");
+    config.apply_env_overrides().unwrap();
+
+    assert_eq!(config.server.listen, "127.0.0.1:7000");
+    assert_eq!(
+        config.server.coalesce,
+        Some(CoalesceCfg {
+            max_frames: 8,
+            ..Default::default()
+        })
+    );
+
+    env::set_var("NEOGROK_RUNTIME_WORKERS", "many");
+    let result = config.apply_env_overrides();
+    assert!(matches!(
+        result,
+        Err(ConfigLoadError::Env {
+            variable: "NEOGROK_RUNTIME_WORKERS",
+            ..
+        })
+    ));
+
+    for variable in [
+        "NEOGROK_SERVER_LISTEN",
+        "NEOGROK_SERVER_COALESCE_MAX_FRAMES",
+        "NEOGROK_RUNTIME_WORKERS",
+    ] {
+        env::remove_var(variable);
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+const fn default_keepalive_misses() -> u32 {
+    3
+}
+
+/// All values are in seconds, missing value disables the
+/// corresponding timeout
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TimeoutsCfg {
+    /// Interval between the echo requests sent to the users
+    /// which negotiated the `ECHO` capability
+    pub keepalive_interval: Option<u64>,
+
+    /// Number of unanswered echo requests after which the
+    /// session is closed
+    #[serde(default = "default_keepalive_misses")]
+    pub keepalive_misses: u32,
+
+    /// Close control session if no frames were sent or
+    /// received for this long
+    pub session_idle: Option<u64>,
+
+    /// Close public client if there was no traffic in both
+    /// directions for this long
+    pub client_idle: Option<u64>,
+
+    /// Idle time before the TCP keepalive probes are sent,
+    /// applies to both users and public clients
+    pub tcp_keepalive: Option<u64>,
+}
+
+impl TimeoutsCfg {
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive_interval.map(Duration::from_secs)
+    }
+
+    pub fn session_idle(&self) -> Option<Duration> {
+        self.session_idle.map(Duration::from_secs)
+    }
+
+    pub fn client_idle(&self) -> Option<Duration> {
+        self.client_idle.map(Duration::from_secs)
+    }
+
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        self.tcp_keepalive.map(Duration::from_secs)
+    }
+}
+
+impl Default for TimeoutsCfg {
+    fn default() -> Self {
+        Self {
+            keepalive_interval: None,
+            keepalive_misses: default_keepalive_misses(),
+            session_idle: None,
+            client_idle: None,
+            tcp_keepalive: None,
+        }
+    }
+}
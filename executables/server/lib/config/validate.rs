@@ -0,0 +1,408 @@
+use std::fmt::{
+    self,
+    Display,
+};
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::Directive;
+
+use super::{
+    compression::CfgCompressionAlgorithm,
+    permissions::ProtocolEntry,
+    Config,
+};
+
+/// Magic from the config shipped with the server
+const DEFAULT_MAGIC: &str = "insecure";
+
+const MAX_DEFLATE_LEVEL: u8 = 12;
+const MAX_ZSTD_LEVEL: u8 = 22;
+const MAX_BROTLI_LEVEL: u8 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Config can be used, but probably not what was
+    /// intended
+    Warning,
+
+    /// Config can't be used
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+
+    /// Dotted TOML path to the offending key
+    pub key: String,
+    pub message: String,
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Issues(Vec<ConfigIssue>);
+
+impl Issues {
+    /// Error unless the insecure settings are explicitly
+    /// allowed
+    fn insecure(
+        &mut self,
+        allowed: bool,
+        key: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        let severity = if allowed {
+            Severity::Warning
+        } else {
+            Severity::Error
+        };
+        self.push(severity, key, message);
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        key: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.0.push(ConfigIssue {
+            severity,
+            key: key.into(),
+            message: message.into(),
+        });
+    }
+
+    fn error(
+        &mut self,
+        key: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.push(Severity::Error, key, message);
+    }
+
+    fn warning(
+        &mut self,
+        key: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.push(Severity::Warning, key, message);
+    }
+}
+
+/// Logs every issue with its severity
+pub fn report(issues: &[ConfigIssue]) {
+    for issue in issues {
+        match issue.severity {
+            Severity::Warning => {
+                tracing::warn!(key = issue.key, "{}", issue.message)
+            }
+            Severity::Error => {
+                tracing::error!(key = issue.key, "{}", issue.message)
+            }
+        }
+    }
+}
+
+fn check_address(issues: &mut Issues, key: &str, address: &str) {
+    if address
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse::<u16>().ok())
+        .is_none()
+    {
+        issues
+            .error(key, format!("{address:?} is not a `host:port` pair"));
+    }
+}
+
+impl Config {
+    /// Checks value ranges, conflicting options and
+    /// insecure settings which can't be expressed by
+    /// the config types themselves.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Issues::default();
+
+        self.validate_server(&mut issues);
+        self.validate_compression(&mut issues);
+        self.validate_permissions(&mut issues);
+        self.validate_access(&mut issues);
+        self.validate_quotas(&mut issues);
+        self.validate_timeouts(&mut issues);
+        self.validate_logging(&mut issues);
+
+        issues.0
+    }
+
+    fn validate_server(&self, issues: &mut Issues) {
+        let server = &self.server;
+
+        check_address(issues, "server.listen", &server.listen);
+        if let Some(listen) = &self.metrics.listen {
+            check_address(issues, "metrics.listen", listen);
+        }
+
+        if server.magic.is_empty() {
+            issues.error(
+                "server.magic",
+                "empty magic lets anyone gain the magic rights",
+            );
+        } else if server.magic == DEFAULT_MAGIC {
+            issues.insecure(
+                server.allow_insecure,
+                "server.magic",
+                "default magic is publicly known, change it",
+            );
+        }
+
+        if server.name.len() > u8::MAX as usize {
+            issues.error(
+                "server.name",
+                format!(
+                    "name is {} bytes long, at most {} is allowed",
+                    server.name.len(),
+                    u8::MAX
+                ),
+            );
+        }
+
+        match server.buffer.read {
+            0 => issues.error("server.buffer.read", "must be positive"),
+            n if n > u16::MAX as usize => issues.error(
+                "server.buffer.read",
+                format!("{n} is greater than {}", u16::MAX),
+            ),
+            _ => {}
+        }
+        if server.buffer.per_client == 0 {
+            issues.error("server.buffer.per_client", "must be positive");
+        }
+        if let Some(coalesce) = &server.coalesce {
+            if coalesce.max_bytes == 0 {
+                issues.error(
+                    "server.coalesce.max_bytes",
+                    "must be positive",
+                );
+            }
+            if coalesce.max_frames == 0 {
+                issues.error(
+                    "server.coalesce.max_frames",
+                    "must be positive",
+                );
+            }
+        }
+
+        if server.watch_interval == Some(0) {
+            issues.error("server.watch_interval", "must be positive");
+        }
+    }
+
+    fn validate_compression(&self, issues: &mut Issues) {
+        let default = &self.compression.default;
+        let max_level = match default.algorithm {
+            CfgCompressionAlgorithm::Deflate => MAX_DEFLATE_LEVEL,
+            CfgCompressionAlgorithm::ZStd => MAX_ZSTD_LEVEL,
+            CfgCompressionAlgorithm::Brotli => MAX_BROTLI_LEVEL,
+            // LZ4 has no levels, any is accepted
+            CfgCompressionAlgorithm::Lz4 => u8::MAX,
+        };
+
+        if default.level > max_level {
+            issues.error(
+                "compression.default.level",
+                format!(
+                    "level {} is out of range 0..={max_level} for the \
+                     {:?} algorithm",
+                    default.level, default.algorithm
+                ),
+            );
+        }
+
+        // Forwarded payloads are read from the public clients
+        let per_client = self.server.buffer.per_client;
+        if default.threshold as usize >= per_client && per_client != 0 {
+            issues.warning(
+                "compression.default.threshold",
+                format!(
+                    "threshold {} is not less than \
+                     server.buffer.per_client, nothing will be compressed",
+                    default.threshold
+                ),
+            );
+        }
+
+        if default.streaming
+            && default.algorithm != CfgCompressionAlgorithm::ZStd
+        {
+            issues.warning(
+                "compression.default.streaming",
+                format!(
+                    "{:?} doesn't support streaming, it won't be offered",
+                    default.algorithm
+                ),
+            );
+        }
+
+        if default.dictionary.is_some()
+            && default.algorithm != CfgCompressionAlgorithm::ZStd
+        {
+            issues.warning(
+                "compression.default.dictionary",
+                format!(
+                    "{:?} doesn't support dictionaries, it won't be \
+                     offered",
+                    default.algorithm
+                ),
+            );
+        }
+
+        if let Some(offload) = &self.compression.offload {
+            if offload.min_size > per_client {
+                issues.warning(
+                    "compression.offload.min_size",
+                    format!(
+                        "min_size {} is greater than \
+                         server.buffer.per_client, nothing will be \
+                         offloaded",
+                        offload.min_size
+                    ),
+                );
+            }
+        }
+
+        if let Some(adaptive) = &default.adaptive {
+            if adaptive.min_ratio <= 1.0 {
+                issues.warning(
+                    "compression.default.adaptive.min_ratio",
+                    "ratio of at most 1.0 never pauses compression",
+                );
+            }
+
+            if adaptive.skip_frames == 0 {
+                issues.error(
+                    "compression.default.adaptive.skip_frames",
+                    "must be positive",
+                );
+            } else if adaptive.max_skip_frames < adaptive.skip_frames {
+                issues.error(
+                    "compression.default.adaptive.max_skip_frames",
+                    format!(
+                        "{} is less than skip_frames {}",
+                        adaptive.max_skip_frames, adaptive.skip_frames
+                    ),
+                );
+            }
+        }
+    }
+
+    fn validate_permissions(&self, issues: &mut Issues) {
+        let base = &self.permissions.base.can;
+        if base.select != ProtocolEntry::NONE {
+            issues.insecure(
+                self.server.allow_insecure,
+                "permissions.base.can.select",
+                "users without magic can choose the port to listen on",
+            );
+        }
+
+        let base = self.permissions.base.to_protocol_rights();
+        let magic = self.permissions.magic.to_protocol_rights();
+        if !magic.contains(base) {
+            issues.warning(
+                "permissions.magic.can",
+                format!(
+                    "magic group lacks rights of the base group: {:?}",
+                    base - magic
+                ),
+            );
+        }
+    }
+
+    fn validate_access(&self, issues: &mut Issues) {
+        for network in &self.access.allow {
+            if self.access.deny.contains(network) {
+                issues.warning(
+                    "access.allow",
+                    format!("{network} is also denied, deny wins"),
+                );
+            }
+        }
+    }
+
+    fn validate_quotas(&self, issues: &mut Issues) {
+        let groups =
+            [("base", &self.quotas.base), ("magic", &self.quotas.magic)];
+        for (group, entry) in groups {
+            let Some(traffic) = entry.traffic else {
+                continue;
+            };
+            let key = format!("quotas.{group}.traffic");
+
+            if traffic.period == 0 {
+                issues.error(format!("{key}.period"), "must be positive");
+            }
+            if traffic.limit == 0 {
+                issues.warning(
+                    format!("{key}.limit"),
+                    "zero limit blocks all traffic",
+                );
+            }
+        }
+    }
+
+    fn validate_timeouts(&self, issues: &mut Issues) {
+        let timeouts = &self.timeouts;
+        let seconds = [
+            ("keepalive_interval", timeouts.keepalive_interval),
+            ("session_idle", timeouts.session_idle),
+            ("client_idle", timeouts.client_idle),
+            ("tcp_keepalive", timeouts.tcp_keepalive),
+        ];
+        for (key, value) in seconds {
+            if value == Some(0) {
+                issues
+                    .error(format!("timeouts.{key}"), "must be positive");
+            }
+        }
+
+        if timeouts.keepalive_misses == 0 {
+            issues.error("timeouts.keepalive_misses", "must be positive");
+        }
+
+        if let (Some(interval), Some(idle)) =
+            (timeouts.keepalive_interval, timeouts.session_idle)
+        {
+            if idle <= interval {
+                issues.warning(
+                    "timeouts.session_idle",
+                    "not greater than keepalive_interval, sessions will \
+                     be closed between the echo requests",
+                );
+            }
+        }
+    }
+
+    fn validate_logging(&self, issues: &mut Issues) {
+        let logging = &self.logging;
+        if logging.level.parse::<LevelFilter>().is_err() {
+            issues.error(
+                "logging.level",
+                format!("unknown level {:?}", logging.level),
+            );
+        }
+
+        for (module, level) in &logging.filters {
+            let key = format!("logging.filters.{module:?}");
+            if level.parse::<LevelFilter>().is_err() {
+                issues.error(key, format!("unknown level {level:?}"));
+            } else if let Err(error) =
+                format!("{module}={level}").parse::<Directive>()
+            {
+                issues.error(key, format!("invalid filter: {error}"));
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use neogrok_protocol::compression::offload::CompressionPool;
+use tokio::sync::watch;
+
+use crate::{
+    audit::AuditLog,
+    config::{
+        compression::OffloadCfg,
+        Config,
+    },
+    proxy::rejected::RejectedClients,
+    quota::Quotas,
+    sessions::Sessions,
+    shaping::Shaping,
+    shutdown::Shutdown,
+};
+
+/// Server-wide state shared between all sessions
+#[derive(Debug)]
+pub struct Context {
+    pub quotas: Quotas,
+    pub shaping: Shaping,
+
+    pub shutdown: Shutdown,
+    pub audit: AuditLog,
+    pub sessions: Sessions,
+    pub rejected: Arc<RejectedClients>,
+
+    /// Shared by all sessions, if offloading is enabled
+    pub compression_pool: Option<CompressionPool>,
+
+    config: watch::Sender<Arc<Config>>,
+}
+
+impl Context {
+    /// Currently active config, new sessions should use it
+    pub fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config.borrow())
+    }
+
+    /// Receiver is notified about every config reload
+    pub fn subscribe_config(&self) -> watch::Receiver<Arc<Config>> {
+        self.config.subscribe()
+    }
+
+    pub fn replace_config(&self, config: Arc<Config>) {
+        self.config.send_replace(config);
+    }
+
+    pub fn new(config: Arc<Config>, audit: AuditLog) -> Self {
+        Self {
+            quotas: Quotas::new(&config.quotas),
+            shaping: Shaping::new(&config.shaping),
+            shutdown: Shutdown::new(),
+            audit,
+            sessions: Sessions::default(),
+            rejected: Arc::default(),
+            compression_pool: config
+                .compression
+                .offload
+                .map(OffloadCfg::to_pool),
+            config: watch::channel(config).0,
+        }
+    }
+}
@@ -1,9 +1,8 @@
-use std::net::SocketAddr;
-
 use integral_enum::IntegralEnum;
 use neogrok_protocol::{
     compression::types::CompressionStrategy,
     hisui::writer::HisuiWriter,
+    protocol::types::Capabilities,
 };
 use tokio::io::AsyncWriteExt;
 
@@ -19,45 +18,105 @@ pub enum CommandHandleResult {
     Ok,
 }
 
-pub async fn handle_command<Writer>(
+/// Handles `command` and the ones queued after it, at most
+/// `batch` in total, so the coalescing writer can send them
+/// at once
+pub async fn handle_commands<Writer>(
     writer: &mut HisuiWriter<Writer>,
-    address: &SocketAddr,
     state: &mut State,
 
     command: MasterCommand,
-    with_threshold: u16,
+    strategy: CompressionStrategy,
+    capabilities: Capabilities,
+    batch: usize,
+) -> CommandHandleResult
+where
+    Writer: AsyncWriteExt + Unpin,
+{
+    let mut next = Some(command);
+    let mut handled = 0;
+    while let Some(command) = next {
+        if handle_command(writer, state, command, strategy, capabilities)
+            .await
+            == CommandHandleResult::Terminate
+        {
+            return CommandHandleResult::Terminate;
+        }
+
+        handled += 1;
+        next = if handled < batch {
+            state.rx.try_recv().ok()
+        } else {
+            None
+        };
+    }
+
+    CommandHandleResult::Ok
+}
+
+async fn handle_command<Writer>(
+    writer: &mut HisuiWriter<Writer>,
+    state: &mut State,
+
+    command: MasterCommand,
+    strategy: CompressionStrategy,
+    capabilities: Capabilities,
 ) -> CommandHandleResult
 where
     Writer: AsyncWriteExt + Unpin,
 {
     match command {
         MasterCommand::Closed => {
-            tracing::error!(
-                ?address,
-                "unexpected behavior: listener closed"
-            );
+            tracing::error!("unexpected behavior: listener closed");
             return CommandHandleResult::Terminate;
         }
 
-        MasterCommand::Connected { id, tx } => {
+        MasterCommand::Error { error } => {
+            tracing::warn!(%error, "reporting error");
+            let Ok(_) = writer.respond_error(error).await else {
+                return CommandHandleResult::Terminate;
+            };
+        }
+
+        MasterCommand::Connected { id, tx, metadata } => {
             state.insert_slave(id, tx);
-            let Ok(_) = writer.write_connect(id).await else {
+            let result =
+                if capabilities.contains(Capabilities::CONNECT_METADATA) {
+                    writer
+                        .write_connect_with_metadata(id.id, &metadata)
+                        .await
+                } else {
+                    writer.write_connect(id.id).await
+                };
+            let Ok(_) = result else {
                 return CommandHandleResult::Terminate;
             };
         }
 
         MasterCommand::Disconnected { id } => {
-            let Ok(_) = writer.write_disconnect(id).await else {
+            // Already disconnected by the user
+            if !state.remove_current(id) {
+                return CommandHandleResult::Ok;
+            }
+
+            let Ok(_) = writer.write_disconnect(id.id).await else {
                 return CommandHandleResult::Terminate;
             };
         }
 
         MasterCommand::Forward { id, buffer } => {
-            let Ok(_) = writer.write_forward(
-                id,
-                &buffer,
-                CompressionStrategy::TryCompress { with_threshold }
-            ).await else {
+            if !state.is_current(id) {
+                tracing::debug!(
+                    client = id.id,
+                    "dropped data of the disconnected client"
+                );
+                return CommandHandleResult::Ok;
+            }
+
+            let Ok(_) = writer
+                .write_forward(id.id, buffer, strategy)
+                .await
+            else {
                 return CommandHandleResult::Terminate;
             };
         }
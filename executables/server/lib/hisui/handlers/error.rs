@@ -28,12 +28,12 @@ where
     Ok(match error {
         ReadError::Io(_) => ErrorType::NonFatalButDisconnect,
         ReadError::FailedToDecompress(err) => {
-            tracing::error!(?err, "Failed to decompress compressed data");
+            tracing::error!(error = %err, "Failed to decompress compressed data");
             ErrorType::Fatal
         }
 
         err => {
-            tracing::error!(?err, "Non-fatal error");
+            tracing::error!(error = %err, "Non-fatal error");
             ErrorType::NonFatal
         }
     })
@@ -1,6 +1,5 @@
 use std::{
     io,
-    net::SocketAddr,
     sync::Arc,
 };
 
@@ -11,26 +10,49 @@ use neogrok_protocol::{
     },
     protocol::{
         error::ProtocolError,
-        types::Rights,
+        types::{
+            Capabilities,
+            Rights,
+        },
     },
 };
 use tokio::{
     io::AsyncWriteExt,
     net::TcpListener,
 };
+use tracing::{
+    Instrument,
+    Span,
+};
 
 use crate::{
+    audit::AuditEvent,
     commands::SlaveCommand,
     config::{
         compression::CompressionData,
+        permissions::PermissionGroup,
         Config,
     },
+    context::Context,
     hisui::state::{
         SendResult,
         State,
     },
-    proxy::listener::run_tcp_listener,
-    user::User,
+    proxy::{
+        access::{
+            AccessFilter,
+            AccessList,
+            MAX_USER_ACCESS_RULES,
+        },
+        listener::{
+            run_tcp_listener,
+            TunnelPolicy,
+        },
+    },
+    user::{
+        Peer,
+        User,
+    },
 };
 
 macro_rules! with_server {
@@ -39,6 +61,12 @@ macro_rules! with_server {
             Some($state_pat) => {
                 match $state_pat.send_to($id, $frame).await {
                     SendResult::Ok => $ok,
+                    SendResult::Stale => {
+                        tracing::debug!(
+                            client = $id,
+                            "dropped stale frame"
+                        );
+                    }
                     SendResult::NoSuchClient | SendResult::Closed => {
                         $writer
                             .respond_error(ProtocolError::NoSuchClient)
@@ -56,11 +84,18 @@ macro_rules! with_server {
     };
 }
 
+/// Capabilities supported by this server, client gets
+/// intersection of this set and the requested one
+const SUPPORTED_CAPABILITIES: Capabilities =
+    Capabilities::CONNECT_METADATA.union(Capabilities::ECHO);
+
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_frame<Writer>(
     writer: &mut HisuiWriter<Writer>,
     frame: Frame,
     config: &Arc<Config>,
-    address: &SocketAddr,
+    context: &Context,
+    peer: &Peer,
 
     compression_data: &CompressionData,
     buffer_size: u16,
@@ -77,18 +112,29 @@ where
 
         Frame::Disconnect { id } => {
             with_server!(writer, state(id, SlaveCommand::ForceDisconnect) as state => {
-                let from = address;
-                tracing::info!(?id, ?from, "disconnected from the proxy");
+                tracing::info!(client = id, "disconnected by the user");
                 state.remove_client(id);
             })
         }
 
         Frame::ServerRequest { port, .. } => {
+            if context.shutdown.is_initiated() {
+                writer
+                    .respond_error(ProtocolError::ShuttingDown)
+                    .await?;
+                return Ok(());
+            }
+
             // TODO: add protocol selection
             if !user.rights.allowed_to(Rights::CAN_CREATE_TCP) {
-                tracing::error!(
-                    ?address,
-                    "access denied to create tcp server"
+                tracing::error!("access denied to create tcp server");
+                context.audit.record(
+                    peer,
+                    user.group,
+                    AuditEvent::PermissionDenied {
+                        right: Rights::CAN_CREATE_TCP,
+                        requested_port: port,
+                    },
                 );
                 writer
                     .respond_error(ProtocolError::AccessDenied)
@@ -99,9 +145,14 @@ where
             // TODO: add protocol selection
             if port != 0 && !user.rights.allowed_to(Rights::CAN_SELECT_TCP)
             {
-                tracing::error!(
-                    ?address,
-                    "access denied to select tcp port"
+                tracing::error!(port, "access denied to select tcp port");
+                context.audit.record(
+                    peer,
+                    user.group,
+                    AuditEvent::PermissionDenied {
+                        right: Rights::CAN_SELECT_TCP,
+                        requested_port: port,
+                    },
                 );
                 writer
                     .respond_error(ProtocolError::AccessDenied)
@@ -109,6 +160,24 @@ where
                 return Ok(());
             }
 
+            // Session holds one tunnel, the replaced one must
+            // release its slot first
+            if let Some(old) = state.take() {
+                context.audit.record(
+                    peer,
+                    user.group,
+                    AuditEvent::TunnelClosed { port: old.port() },
+                );
+            }
+
+            let Some(permit) = user.quota.try_acquire_tunnel() else {
+                tracing::error!("tunnels quota exceeded");
+                writer
+                    .respond_error(ProtocolError::TunnelQuotaExceeded)
+                    .await?;
+                return Ok(());
+            };
+
             let listener = match TcpListener::bind(&format!(
                 "0.0.0.0:{port}"
             ))
@@ -117,6 +186,7 @@ where
                 Ok(l) => l,
                 Err(error) => {
                     tracing::error!(
+                        port,
                         %error,
                         "failed to create tcp listener"
                     );
@@ -129,7 +199,7 @@ where
                 Ok(a) => a,
                 Err(error) => {
                     tracing::error!(
-                        ?error,
+                        %error,
                         "failed to get listener address"
                     );
                     writer
@@ -139,17 +209,52 @@ where
                 }
             };
 
-            let (new_state, token) = State::new();
-            tokio::spawn(run_tcp_listener(
-                listener,
-                *address,
-                new_state.clone_pool(),
-                new_state.clone_tx(),
-                token,
-                config.server.buffer.per_client,
-            ));
+            let (new_state, token) =
+                State::new(permit, newly_created_address.port());
+            let span = tracing::info_span!(
+                "tunnel",
+                port = newly_created_address.port()
+            );
+            tokio::spawn(
+                run_tcp_listener(
+                    listener,
+                    new_state.clone_pool(),
+                    new_state.clone_tx(),
+                    token,
+                    TunnelPolicy {
+                        access: AccessFilter::new(
+                            AccessList::from_cfg(&config.access),
+                            user.access.clone(),
+                        ),
+                        quota: Arc::clone(new_state.permit().quota()),
+                        shaper: context
+                            .shaping
+                            .tunnel_shaper(&user.shaper, user.group),
+                        client_rate: context
+                            .shaping
+                            .client_rate(user.group),
+                        client_idle: config.timeouts.client_idle(),
+                        tcp_keepalive: config.timeouts.tcp_keepalive(),
+                        rejected: Arc::clone(&context.rejected),
+                    },
+                    config.server.buffer.per_client,
+                )
+                .instrument(span),
+            );
 
-            tracing::info!(?newly_created_address, "Created server");
+            tracing::info!(
+                port = newly_created_address.port(),
+                "Created server"
+            );
+            context.audit.record(
+                peer,
+                user.group,
+                AuditEvent::TunnelCreated {
+                    protocol: "tcp",
+                    port: newly_created_address.port(),
+                    requested_port: port,
+                },
+            );
 
             *state = Some(new_state);
             writer
@@ -161,29 +266,112 @@ where
             if magic == config.server.magic {
                 let new_rights =
                     config.permissions.magic.to_protocol_rights();
+                if user.group != PermissionGroup::Magic {
+                    // Tunnels created before keep the old limits
+                    user.quota = context
+                        .quotas
+                        .user(PermissionGroup::Magic, peer.address.ip());
+                    user.shaper = context
+                        .shaping
+                        .user_shaper(PermissionGroup::Magic);
+                }
+                user.group = PermissionGroup::Magic;
                 user.rights = new_rights;
-                tracing::info!(
-                    ?address,
-                    ?new_rights,
-                    "authorized through magic"
+                Span::current().record("group", user.group.name());
+                tracing::info!(?new_rights, "authorized through magic");
+                context.audit.record(
+                    peer,
+                    user.group,
+                    AuditEvent::AuthSucceeded { rights: new_rights },
                 );
 
                 writer.respond_update_rights(new_rights).await?;
             } else {
-                tracing::error!(?address, %magic, "failed to authorize using magic");
+                tracing::error!("failed to authorize using magic");
+                context.audit.record(
+                    peer,
+                    user.group,
+                    AuditEvent::AuthFailed,
+                );
                 writer
                     .respond_error(ProtocolError::InvalidCredentials)
                     .await?;
             }
         }
 
+        Frame::AccessRule { action, network } => {
+            if user.access.len() >= MAX_USER_ACCESS_RULES {
+                tracing::error!("access rules limit exceeded");
+                writer
+                    .respond_error(ProtocolError::TooManyAccessRules)
+                    .await?;
+                return Ok(());
+            }
+
+            tracing::info!(?action, %network, "added access rule");
+            user.access.push(action, network);
+        }
+
+        // Forward frames sent by the user before it received the
+        // response would be decompressed with the new context
+        Frame::Capabilities { .. } | Frame::Dictionary { .. }
+            if state.is_some() =>
+        {
+            tracing::error!(
+                "compression renegotiation after the tunnel creation"
+            );
+            writer
+                .respond_error(ProtocolError::UnexpectedFrame)
+                .await?;
+        }
+
+        Frame::Capabilities { capabilities } => {
+            let mut supported = SUPPORTED_CAPABILITIES;
+            if compression_data.supports_streaming() {
+                supported |= Capabilities::STREAM_COMPRESSION;
+            }
+
+            let negotiated = capabilities & supported;
+            user.capabilities = negotiated;
+            tracing::info!(?negotiated, "negotiated capabilities");
+
+            writer.respond_capabilities(negotiated).await?;
+        }
+
+        Frame::Dictionary { id: 0 } => {
+            user.dictionary = false;
+            writer.respond_dictionary(0).await?;
+        }
+
+        Frame::Dictionary { id } => {
+            let available = compression_data.dictionary().map(|d| d.id());
+            if available == Some(id) {
+                user.dictionary = true;
+                tracing::info!(id, "negotiated compression dictionary");
+                writer.respond_dictionary(id).await?;
+            } else {
+                tracing::warn!(
+                    requested = id,
+                    ?available,
+                    "compression dictionary mismatch"
+                );
+                writer
+                    .respond_error(ProtocolError::DictionaryMismatch)
+                    .await?;
+            }
+        }
+
+        Frame::EchoRequest { nonce, timestamp } => {
+            writer.respond_echo(nonce, timestamp).await?;
+        }
+
         Frame::PingRequest => {
-            tracing::info!(?address, "ping request");
+            tracing::info!("ping request");
 
             writer
                 .respond_ping(
                     &config.server.name,
-                    compression_data.algorithm.to_protocol(),
+                    compression_data.algorithm.id(),
                     compression_data.level,
                     buffer_size,
                 )
@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    /// Send echo request with these nonce and timestamp
+    Send { nonce: u32, timestamp: u64 },
+
+    /// Too many requests were left unanswered
+    Dead,
+}
+
+/// Tracks echo requests sent to the user. Timestamps are
+/// microseconds since the creation of the tracker.
+#[derive(Debug)]
+pub struct Keepalive {
+    started: Instant,
+    nonce: u32,
+
+    unanswered: u32,
+    max_unanswered: u32,
+}
+
+impl Keepalive {
+    pub fn probe(&mut self) -> Probe {
+        if self.unanswered >= self.max_unanswered {
+            return Probe::Dead;
+        }
+
+        self.nonce = self.nonce.wrapping_add(1);
+        self.unanswered += 1;
+
+        Probe::Send {
+            nonce: self.nonce,
+            timestamp: self.now(),
+        }
+    }
+
+    /// Returns round-trip time of the request. Only
+    /// response to the last sent request resets
+    /// unanswered counter, but late responses are still
+    /// measured.
+    pub fn acknowledge(
+        &mut self,
+        nonce: u32,
+        timestamp: u64,
+    ) -> Option<Duration> {
+        let now = self.now();
+        if timestamp > now {
+            // Surely not ours
+            return None;
+        }
+
+        if nonce == self.nonce {
+            self.unanswered = 0;
+        }
+        Some(Duration::from_micros(now - timestamp))
+    }
+
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64
+    }
+
+    pub fn new(max_unanswered: u32) -> Self {
+        Self {
+            started: Instant::now(),
+            nonce: 0,
+            unanswered: 0,
+            max_unanswered,
+        }
+    }
+}
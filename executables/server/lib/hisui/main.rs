@@ -1,50 +1,108 @@
 use std::{
-    net::SocketAddr,
     num::NonZeroU16,
     sync::Arc,
 };
 
-use neogrok_protocol::hisui::{
-    error::ReadError,
-    reader::HisuiReader,
-    writer::HisuiWriter,
+use neogrok_protocol::{
+    hisui::{
+        error::ReadError,
+        frame::Frame,
+        reader::HisuiReader,
+        utils::replace_compression,
+        writer::HisuiWriter,
+    },
+    protocol::types::Capabilities,
 };
-use tokio::io::{
-    AsyncReadExt,
-    AsyncWriteExt,
+use tokio::{
+    io::{
+        AsyncReadExt,
+        AsyncWriteExt,
+    },
+    time::{
+        Instant,
+        Interval,
+    },
 };
 
 use crate::{
+    audit::AuditEvent,
     commands::MasterCommand,
-    config::Config,
+    config::{
+        permissions::PermissionGroup,
+        Config,
+    },
+    context::Context,
     hisui::{
         handlers::{
             command::*,
             error::*,
             frame::*,
         },
+        keepalive::{
+            Keepalive,
+            Probe,
+        },
         state::State,
     },
     infinite_future::infinite_future,
-    user::User,
+    shutdown::ShutdownListener,
+    user::{
+        Peer,
+        User,
+    },
+    utils::idle_deadline,
 };
 
 pub async fn listen_hisui_client<Reader, Writer>(
     mut reader: HisuiReader<Reader>,
     mut writer: HisuiWriter<Writer>,
 
-    config: Arc<Config>,
-    address: SocketAddr,
+    mut config: Arc<Config>,
+    context: Arc<Context>,
+    mut shutdown: ShutdownListener,
+    peer: Peer,
 
     buffer_read: u16,
 ) where
     Reader: AsyncReadExt + Unpin,
     Writer: AsyncWriteExt + Unpin,
 {
-    let compression_data = &config.compression.default;
-    let mut user = User::new(config.permissions.base.to_protocol_rights());
+    // Compression can't be changed for the running session, so
+    // initial config is used for it
+    let session_config = Arc::clone(&config);
+    let compression_data = &session_config.compression.default;
+    let strategy = compression_data.strategy();
+    let mut compression_stats = writer.compression_stats();
+    // Queued commands are handled one by one unless the
+    // writer can coalesce them
+    let command_batch = session_config
+        .server
+        .coalesce
+        .as_ref()
+        .map_or(1, |coalesce| coalesce.max_frames);
+    let mut config_updates = context.subscribe_config();
+    let _registered = context.sessions.register(&peer);
+
+    let mut user = User::new(
+        PermissionGroup::Base,
+        config.permissions.base.to_protocol_rights(),
+        context
+            .quotas
+            .user(PermissionGroup::Base, peer.address.ip()),
+        context.shaping.user_shaper(PermissionGroup::Base),
+    );
     let mut state: Option<State> = None;
     let buffer_read = NonZeroU16::new(buffer_read);
+    let mut shutting_down = false;
+
+    let timeouts = &session_config.timeouts;
+    let session_idle = timeouts.session_idle();
+    let mut last_activity = Instant::now();
+    let mut keepalive = Keepalive::new(timeouts.keepalive_misses);
+    let mut keepalive_timer =
+        timeouts.keepalive_interval().map(|period| {
+            tokio::time::interval_at(Instant::now() + period, period)
+        });
 
     async fn wait_command(
         state: &mut Option<State>,
@@ -58,38 +116,114 @@ pub async fn listen_hisui_client<Reader, Writer>(
         }
     }
 
+    async fn tick(timer: &mut Option<Interval>) {
+        match timer {
+            Some(timer) => {
+                timer.tick().await;
+            }
+            None => {
+                infinite_future().await;
+            }
+        }
+    }
+
     loop {
+        // Everything written during the previous iteration
+        if writer.flush().await.is_err() {
+            break;
+        }
+
         tokio::select! {
+            _ = idle_deadline(last_activity, session_idle) => {
+                tracing::warn!("session is idle for too long, closing");
+                break;
+            }
+
+            _ = tick(&mut keepalive_timer), if user.capabilities.contains(Capabilities::ECHO) => {
+                match keepalive.probe() {
+                    Probe::Send { nonce, timestamp } => {
+                        if writer.request_echo(nonce, timestamp).await.is_err() {
+                            break;
+                        }
+                    }
+                    Probe::Dead => {
+                        tracing::warn!("user stopped answering echo requests, closing");
+                        break;
+                    }
+                }
+            }
+
+            _ = shutdown.initiated(), if !shutting_down => {
+                shutting_down = true;
+                if let Some(state) = state.as_mut() {
+                    state.stop_listener();
+                }
+
+                let drain_timeout = config.server.drain_timeout;
+                if writer.write_shutdown_notice(drain_timeout).await.is_err() {
+                    break;
+                }
+            }
+
+            Ok(()) = config_updates.changed() => {
+                config = Arc::clone(&config_updates.borrow_and_update());
+
+                let rights = config.permissions.group(user.group).to_protocol_rights();
+                if rights != user.rights {
+                    user.rights = rights;
+                    tracing::info!(?rights, "rights updated after config reload");
+                    context.audit.record(
+                        &peer,
+                        user.group,
+                        AuditEvent::RightsChanged { rights },
+                    );
+
+                    if writer.respond_update_rights(rights).await.is_err() {
+                        break;
+                    }
+                }
+            }
+
             command = wait_command(&mut state) => {
                 let Some(command) = command else {
                     tracing::error!("master receiver is dropped (report this on project page)");
                     break;
                 };
 
-                if handle_command(
+                last_activity = Instant::now();
+                if handle_commands(
                     &mut writer,
-                    &address,
                     state.as_mut().unwrap(),
                     command,
-                    compression_data.threshold,
+                    strategy,
+                    user.capabilities,
+                    command_batch,
                 ).await == CommandHandleResult::Terminate {
                     break;
                 }
+
+                if writer.compression_stats() != compression_stats {
+                    compression_stats = writer.compression_stats();
+                    context.sessions.record_compression(peer.session, compression_stats);
+                }
             }
 
             frame_type = reader.read_packet_type() => {
                 let (pkt_type, flags) = match frame_type {
-                    Ok(d) => d,
+                    Ok(d) => {
+                        last_activity = Instant::now();
+                        d
+                    }
                     Err(error) => {
                         let Ok(error_type) = handle_error(
                             &mut writer,
                             &error,
-                            &address
+                            &peer.address
                         ).await else { break };
                         match error_type {
                             ErrorType::NonFatalButDisconnect => break,
                             ErrorType::Fatal => {
-                                tracing::error!(?address, ?error, "fatal error");
+                                tracing::error!(%error, "fatal error");
                                 break;
                             }
 
@@ -105,16 +239,33 @@ pub async fn listen_hisui_client<Reader, Writer>(
                 ).await {
                     Ok(f) => f,
                     Err(e) => {
-                        tracing::error!(%e, "failed to read frame");
+                        tracing::error!(error = %e, "failed to read frame");
                         break;
                     }
                 };
 
+                let frame = match frame {
+                    Frame::EchoResponse { nonce, timestamp } => {
+                        match keepalive.acknowledge(nonce, timestamp) {
+                            Some(rtt) => {
+                                tracing::debug!(?rtt, "echo response");
+                                context.sessions.record_rtt(peer.session, rtt);
+                            }
+                            None => tracing::debug!(nonce, "invalid echo response"),
+                        }
+                        continue;
+                    }
+
+                    frame => frame,
+                };
+
+                let negotiated = user.negotiated_compression();
                 match handle_frame(
                     &mut writer,
                     frame,
                     &config,
-                    &address,
+                    &context,
+                    &peer,
                     compression_data,
                     buffer_read.map(|i| i.get()).unwrap_or(u16::MAX),
                     &mut user,
@@ -122,15 +273,52 @@ pub async fn listen_hisui_client<Reader, Writer>(
                 ).await {
                     Ok(()) => {},
                     Err(e) => {
-                        tracing::error!(%e, "failed to handle frame");
+                        tracing::error!(error = %e, "failed to handle frame");
                         let Ok(_) = handle_error(
                             &mut writer,
                             &ReadError::Io(e),
-                            &address
+                            &peer.address
                         ).await else { break };
                     }
                 }
+
+                // Compression changes right after the negotiation
+                // response. It's allowed only before the tunnel
+                // is created, so no forwarded data is in flight
+                let (streaming, dictionary) = user.negotiated_compression();
+                if (streaming, dictionary) != negotiated {
+                    let (compressor, decompressor) =
+                        compression_data.to_session_pair(streaming, dictionary);
+                    replace_compression(&mut reader, &mut writer, compressor, decompressor);
+                    tracing::debug!(streaming, dictionary, "switched compression");
+                }
             }
         }
+
+        // Session is drained when there is no public clients left
+        if shutting_down && state.as_ref().is_none_or(|s| s.clients() == 0)
+        {
+            tracing::info!("session drained");
+            break;
+        }
+    }
+
+    // Last responses, e.g. the error which closed the session
+    writer.flush().await.unwrap_or_default();
+
+    if compression_stats.skipped != 0 {
+        tracing::info!(
+            attempted = compression_stats.attempted,
+            skipped = compression_stats.skipped,
+            "compression attempts skipped"
+        );
+    }
+
+    if let Some(state) = state {
+        context.audit.record(
+            &peer,
+            user.group,
+            AuditEvent::TunnelClosed { port: state.port() },
+        );
     }
 }
@@ -4,3 +4,4 @@ pub mod server;
 pub mod state;
 
 pub mod handlers;
+pub mod keepalive;
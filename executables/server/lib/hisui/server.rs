@@ -1,89 +1,166 @@
 use std::{
     io,
     sync::Arc,
+    time::Duration,
 };
 
-use neogrok_protocol::{
-    compression::algorithms::polymorphic::{
-        BufCompressor,
-        BufDecompressor,
-    },
-    hisui::{
-        reader::HisuiReader,
-        writer::HisuiWriter,
-    },
+use neogrok_protocol::hisui::{
+    reader::HisuiReader,
+    writer::HisuiWriter,
 };
-use tokio::{
-    io::{
-        AsyncRead,
-        BufReader,
-    },
-    net::TcpListener,
+use tokio::net::TcpListener;
+use tracing::{
+    Instrument,
+    Span,
 };
 
 use crate::{
-    config::Config,
+    audit::AuditLog,
+    config::{
+        overrides::ConfigSource,
+        permissions::PermissionGroup,
+        Config,
+    },
+    context::Context,
     hisui::main::listen_hisui_client,
+    metrics::serve_metrics,
+    reload::{
+        reload_config,
+        ReloadTrigger,
+    },
+    shutdown::shutdown_signal,
+    user::Peer,
+    utils::set_tcp_keepalive,
 };
 
-pub async fn listen_hisui(config: Arc<Config>) -> io::Result<()> {
+pub async fn listen_hisui(
+    config: Arc<Config>,
+    config_source: ConfigSource,
+) -> io::Result<()> {
     let listener = TcpListener::bind(&config.server.listen).await?;
     let addr = listener.local_addr()?;
     tracing::info!(%addr, "started Neogrok main server");
 
+    let mut reload = ReloadTrigger::new(
+        config_source,
+        config
+            .server
+            .watch_interval
+            .map(Duration::from_secs),
+    );
+    let audit = AuditLog::open(&config.audit).inspect_err(|error| {
+        tracing::error!(%error, "failed to open audit log");
+    })?;
+    let context = Arc::new(Context::new(Arc::clone(&config), audit));
+    if let Some(listen) = &config.metrics.listen {
+        let metrics = TcpListener::bind(listen).await.inspect_err(|error| {
+            tracing::error!(%error, %listen, "failed to bind metrics endpoint");
+        })?;
+        tracing::info!(addr = %metrics.local_addr()?, "started metrics endpoint");
+        tokio::spawn(serve_metrics(metrics, Arc::clone(&context)));
+    }
+    let signal = shutdown_signal();
+    tokio::pin!(signal);
+    let mut sessions: u64 = 0;
+
     loop {
-        let (mut stream, addr) = listener.accept().await?;
-        tracing::info!(%addr, "new user connected to the main server");
+        let (mut stream, addr) = tokio::select! {
+            _ = &mut signal => break,
+            _ = reload.triggered() => {
+                let path = &reload.source().path;
+                match reload_config(reload.source(), &context) {
+                    Ok(()) => tracing::info!(path = %path.display(), "config reloaded"),
+                    Err(error) => tracing::error!(
+                        path = %path.display(),
+                        %error,
+                        "failed to reload config, keeping the old one"
+                    ),
+                }
+                continue;
+            }
+            accepted = listener.accept() => accepted?,
+        };
+        sessions += 1;
+        let peer = Peer {
+            session: sessions,
+            address: addr,
+        };
+        let span = tracing::info_span!(
+            "session",
+            session = peer.session,
+            address = %peer.address,
+            group = PermissionGroup::Base.name(),
+        );
+        let _entered = span.enter();
+        tracing::info!("new user connected to the main server");
 
         if let Err(error) = stream.set_nodelay(true) {
-            tracing::error!(%error, ?addr, "failed to set TCP nodelay, closing connection");
+            tracing::error!(%error, "failed to set TCP nodelay, closing connection");
             continue;
         }
 
-        let config = Arc::clone(&config);
-        let buffer_read: u16 =
-            if let Ok(u) = config.server.buffer.read.try_into() {
-                u
-            } else {
-                tracing::error!(
-                    "Read buffer doesen't fit in 16bit unsigned int, \
-                     falling back to the 4KB"
-                );
-                4096
-            };
+        let config = context.config();
+        if let Some(idle) = config.timeouts.tcp_keepalive() {
+            if let Err(error) = set_tcp_keepalive(&stream, idle) {
+                tracing::warn!(%error, "failed to set TCP keepalive");
+            }
+        }
+        let context = Arc::clone(&context);
+        let shutdown = context.shutdown.subscribe();
+        // Range is checked by the config validation
+        let buffer_read = config.server.buffer.read as u16;
 
-        tokio::spawn(async move {
-            let (reader, writer) = stream.split();
-            let (comp, decomp) = config.compression.default.to_pair();
-            let (reader, writer) = create_rw_handles(
-                reader,
-                writer,
-                comp,
-                decomp,
-                (buffer_read as usize) + 5, // +5 for header
-            );
+        tokio::spawn(
+            async move {
+                let (reader, writer) = stream.split();
+                let (comp, decomp) = config.compression.default.to_pair();
+                let mut reader = HisuiReader::server(reader, decomp);
+                let mut writer = HisuiWriter::new(writer, comp);
+                if let Some(pool) = &context.compression_pool {
+                    reader.offload_to(pool.clone());
+                    writer.offload_to(pool.clone());
+                }
+                if let Some(coalesce) = &config.server.coalesce {
+                    writer.coalesce(coalesce.policy());
+                }
 
-            listen_hisui_client(reader, writer, config, addr, buffer_read)
+                listen_hisui_client(
+                    reader,
+                    writer,
+                    config,
+                    context,
+                    shutdown,
+                    peer,
+                    buffer_read,
+                )
                 .await;
-            tracing::info!(?addr, "disconnected from the main server");
-        });
+                tracing::info!("disconnected from the main server");
+            }
+            .instrument(Span::clone(&span)),
+        );
     }
-}
 
-fn create_rw_handles<Reader: AsyncRead, Writer>(
-    reader: Reader,
-    writer: Writer,
+    drop(listener);
+    drain(&context.config(), &context).await;
 
-    compressor: BufCompressor,
-    decompressor: BufDecompressor,
+    Ok(())
+}
 
-    buffer_size: usize,
-) -> (HisuiReader<BufReader<Reader>>, HisuiWriter<Writer>) {
-    let reader = HisuiReader::server(
-        BufReader::with_capacity(buffer_size, reader),
-        decompressor,
+async fn drain(config: &Config, context: &Context) {
+    let drain_timeout = config.server.drain_timeout;
+    context.shutdown.initiate();
+    tracing::info!(
+        sessions = context.shutdown.sessions(),
+        drain_timeout,
+        "shutting down, draining connections"
     );
-    let writer = HisuiWriter::new(writer, compressor);
 
-    (reader, writer)
+    let timeout = Duration::from_secs(drain_timeout as u64);
+    match tokio::time::timeout(timeout, context.shutdown.drained()).await {
+        Ok(()) => tracing::info!("all connections are drained"),
+        Err(_) => tracing::warn!(
+            sessions = context.shutdown.sessions(),
+            "drain timeout exceeded, closing remaining connections"
+        ),
+    }
 }
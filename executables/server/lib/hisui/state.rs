@@ -1,6 +1,6 @@
 use std::{
-    mem,
     sync::Arc,
+    time::Duration,
 };
 
 use flume::{
@@ -8,13 +8,13 @@ use flume::{
     Receiver,
     Sender,
 };
-use idpool::prelude::FlatIdPool;
+use idpool::prelude::{
+    ConcurrentGenerationalIdPool,
+    GenerationalId,
+};
 use integral_enum::IntegralEnum;
 use rustc_hash::FxHashMap;
-use tokio::sync::{
-    oneshot,
-    Mutex,
-};
+use tokio::sync::oneshot;
 
 use crate::{
     commands::{
@@ -22,33 +22,94 @@ use crate::{
         ShutdownToken,
         SlaveCommand,
     },
+    quota::TunnelPermit,
     utils::cold_path,
 };
 
+/// Freed client ids are not reused for this long, so frames
+/// sent by the user before it received `Disconnect` don't
+/// reach the new client
+pub const ID_QUARANTINE: Duration = Duration::from_secs(30);
+
+pub type ClientIdPool = ConcurrentGenerationalIdPool<u16>;
+pub type ClientId = GenerationalId<u16>;
+
 #[derive(IntegralEnum)]
 pub enum SendResult {
     Ok,
     NoSuchClient,
+
+    /// Client with this id was recently disconnected
+    Stale,
     Closed,
 }
 
+struct Slave {
+    generation: u32,
+    tx: Sender<SlaveCommand>,
+}
+
 pub struct State {
-    slaves: FxHashMap<u16, Sender<SlaveCommand>>,
+    slaves: FxHashMap<u16, Slave>,
 
     pub rx: Receiver<MasterCommand>,
     tx: Sender<MasterCommand>,
 
     token: Option<oneshot::Sender<ShutdownToken>>,
-    pool: Arc<Mutex<FlatIdPool<u16>>>,
+    pool: Arc<ClientIdPool>,
+
+    permit: TunnelPermit,
+    port: u16,
 }
 
 impl State {
-    pub fn insert_slave(&mut self, id: u16, slave: Sender<SlaveCommand>) {
-        self.slaves.insert(id, slave);
+    pub fn insert_slave(
+        &mut self,
+        id: ClientId,
+        tx: Sender<SlaveCommand>,
+    ) {
+        self.slaves.insert(
+            id.id,
+            Slave {
+                generation: id.generation,
+                tx,
+            },
+        );
+    }
+
+    /// Returns `false` if there was no such client
+    pub fn remove_client(&mut self, id: u16) -> bool {
+        self.slaves.remove(&id).is_some()
+    }
+
+    /// Removes client only if it is of the same generation,
+    /// returns `false` otherwise
+    pub fn remove_current(&mut self, id: ClientId) -> bool {
+        if !self.is_current(id) {
+            return false;
+        }
+
+        self.remove_client(id.id)
+    }
+
+    /// Whether `id` belongs to the connected client and not
+    /// to the previous holder of the same id
+    pub fn is_current(&self, id: ClientId) -> bool {
+        self.slaves
+            .get(&id.id)
+            .is_some_and(|slave| slave.generation == id.generation)
+    }
+
+    pub fn clients(&self) -> usize {
+        self.slaves.len()
     }
 
-    pub fn remove_client(&mut self, id: u16) {
-        self.slaves.remove(&id).expect("Unreachable");
+    /// Stops accepting new public clients, already
+    /// connected clients are kept alive
+    pub fn stop_listener(&mut self) {
+        if let Some(token) = self.token.take() {
+            token.send(ShutdownToken).unwrap_or_default();
+        }
     }
 
     pub async fn send_to(
@@ -56,8 +117,8 @@ impl State {
         id: u16,
         command: SlaveCommand,
     ) -> SendResult {
-        if let Some(tx) = self.slaves.get(&id) {
-            if tx.send_async(command).await.is_err() {
+        if let Some(slave) = self.slaves.get(&id) {
+            if slave.tx.send_async(command).await.is_err() {
                 cold_path();
                 SendResult::Closed
             } else {
@@ -65,7 +126,11 @@ impl State {
             }
         } else {
             cold_path();
-            SendResult::NoSuchClient
+            if self.pool.is_released(id) {
+                SendResult::Stale
+            } else {
+                SendResult::NoSuchClient
+            }
         }
     }
 
@@ -73,11 +138,23 @@ impl State {
         self.tx.clone()
     }
 
-    pub fn clone_pool(&self) -> Arc<Mutex<FlatIdPool<u16>>> {
+    pub fn clone_pool(&self) -> Arc<ClientIdPool> {
         Arc::clone(&self.pool)
     }
 
-    pub fn new() -> (Self, oneshot::Receiver<ShutdownToken>) {
+    pub fn permit(&self) -> &TunnelPermit {
+        &self.permit
+    }
+
+    /// Public port of the tunnel
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn new(
+        permit: TunnelPermit,
+        port: u16,
+    ) -> (Self, oneshot::Receiver<ShutdownToken>) {
         let (tx, rx) = unbounded();
         let (stk, rtk) = oneshot::channel();
 
@@ -87,7 +164,12 @@ impl State {
                 rx,
                 token: Some(stk),
                 slaves: Default::default(),
-                pool: Arc::new(FlatIdPool::zero().into()),
+                pool: Arc::new(ClientIdPool::new(
+                    0..=u16::MAX,
+                    ID_QUARANTINE,
+                )),
+                permit,
+                port,
             },
             rtk,
         )
@@ -96,7 +178,6 @@ impl State {
 
 impl Drop for State {
     fn drop(&mut self) {
-        let token = mem::take(&mut self.token).unwrap();
-        token.send(ShutdownToken).unwrap_or_default();
+        self.stop_listener();
     }
 }
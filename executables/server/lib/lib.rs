@@ -1,12 +1,23 @@
+pub mod audit;
 pub mod config;
+pub mod context;
 
 pub mod hisui;
 pub mod medusa;
+pub mod metrics;
 
 pub mod proxy;
+pub mod quota;
+pub mod reload;
+pub mod sessions;
+pub mod shaping;
+pub mod shutdown;
 pub mod user;
 
 pub mod commands;
 pub mod utils;
 
 pub mod infinite_future;
+
+#[cfg(test)]
+mod tests;
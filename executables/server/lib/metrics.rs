@@ -0,0 +1,225 @@
+use std::{
+    fmt::Write as _,
+    sync::Arc,
+    time::Duration,
+};
+
+use neogrok_protocol::{
+    compression::adaptive::AdaptiveStats,
+    hisui::rtt::RttStats,
+};
+use tokio::{
+    io::{
+        AsyncReadExt,
+        AsyncWriteExt,
+    },
+    net::{
+        TcpListener,
+        TcpStream,
+    },
+    sync::Semaphore,
+};
+
+use crate::{
+    context::Context,
+    proxy::rejected::RejectReason,
+};
+
+type RttGetter = fn(&RttStats) -> Option<Duration>;
+type CompressionGetter = fn(&AdaptiveStats) -> u64;
+
+/// Requests larger than this are not served
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Connection is closed if it wasn't served in time
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections beyond this are closed right away
+const MAX_CONNECTIONS: usize = 16;
+
+/// Serves metrics in the Prometheus text format, any
+/// request path returns the same metrics
+pub async fn serve_metrics(listener: TcpListener, context: Arc<Context>) {
+    let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    loop {
+        let (stream, address) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(error) => {
+                tracing::error!(%error, "failed to accept metrics request");
+                continue;
+            }
+        };
+
+        let Ok(permit) = Arc::clone(&connections).try_acquire_owned()
+        else {
+            tracing::debug!(%address, "too many metrics connections");
+            continue;
+        };
+
+        let context = Arc::clone(&context);
+        tokio::spawn(async move {
+            let _permit = permit;
+            match tokio::time::timeout(
+                REQUEST_TIMEOUT,
+                respond(stream, &context),
+            )
+            .await
+            {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => {
+                    tracing::debug!(%address, %error, "failed to serve metrics");
+                }
+                Err(_) => {
+                    tracing::debug!(%address, "metrics request timed out");
+                }
+            }
+        });
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    context: &Context,
+) -> std::io::Result<()> {
+    // Request itself is irrelevant, just wait for the end of
+    // the headers
+    let mut request = Vec::with_capacity(512);
+    let mut chunk = [0; 512];
+    while !request.ends_with(b"\r\n\r\n") {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&chunk[..read]);
+    }
+
+    let body = render(context);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; \
+         version=0.0.4\r\nContent-Length: {}\r\nConnection: \
+         close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn render(context: &Context) -> String {
+    let sessions = context.sessions.snapshot();
+    let mut out = String::new();
+
+    metric_header(
+        &mut out,
+        "neogrok_sessions",
+        "gauge",
+        "Connected users",
+    );
+    writeln!(out, "neogrok_sessions {}", sessions.len()).unwrap();
+
+    metric_header(
+        &mut out,
+        "neogrok_rejected_clients_total",
+        "counter",
+        "Public clients rejected by the tunnels",
+    );
+    for reason in RejectReason::ALL {
+        writeln!(
+            out,
+            "neogrok_rejected_clients_total{{reason=\"{}\"}} {}",
+            reason.name(),
+            context.rejected.get(reason)
+        )
+        .unwrap();
+    }
+
+    let rtt_metrics: [(&str, &str, RttGetter); 4] = [
+        (
+            "smoothed",
+            "Smoothed round-trip time of the session",
+            RttStats::smoothed,
+        ),
+        (
+            "variation",
+            "Round-trip time variation of the session",
+            RttStats::variation,
+        ),
+        (
+            "min",
+            "Minimal round-trip time of the session",
+            RttStats::min,
+        ),
+        (
+            "max",
+            "Maximal round-trip time of the session",
+            RttStats::max,
+        ),
+    ];
+    for (kind, help, get) in rtt_metrics {
+        let name = format!("neogrok_session_rtt_{kind}_seconds");
+        metric_header(&mut out, &name, "gauge", help);
+
+        for (session, metrics) in &sessions {
+            let Some(value) = get(&metrics.rtt) else {
+                continue;
+            };
+
+            writeln!(
+                out,
+                "{name}{{session=\"{session}\",address=\"{}\"}} {}",
+                metrics.address,
+                value.as_secs_f64()
+            )
+            .unwrap();
+        }
+    }
+
+    metric_header(
+        &mut out,
+        "neogrok_session_echo_responses_total",
+        "counter",
+        "Echo responses received from the session",
+    );
+    for (session, metrics) in &sessions {
+        writeln!(
+            out,
+            "neogrok_session_echo_responses_total{{session=\"{session}\",\
+             address=\"{}\"}} {}",
+            metrics.address,
+            metrics.rtt.samples()
+        )
+        .unwrap();
+    }
+
+    let compression_metrics: [(&str, &str, CompressionGetter); 2] = [
+        (
+            "attempts",
+            "Payloads of the session passed to the compressor",
+            |stats| stats.attempted,
+        ),
+        (
+            "skipped",
+            "Compression attempts skipped by the adaptive strategy",
+            |stats| stats.skipped,
+        ),
+    ];
+    for (kind, help, get) in compression_metrics {
+        let name = format!("neogrok_session_compression_{kind}_total");
+        metric_header(&mut out, &name, "counter", help);
+
+        for (session, metrics) in &sessions {
+            writeln!(
+                out,
+                "{name}{{session=\"{session}\",address=\"{}\"}} {}",
+                metrics.address,
+                get(&metrics.compression)
+            )
+            .unwrap();
+        }
+    }
+
+    out
+}
+
+fn metric_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}").unwrap();
+}
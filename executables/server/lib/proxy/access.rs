@@ -0,0 +1,99 @@
+use std::net::IpAddr;
+
+use ipnet::{
+    IpNet,
+    Ipv4Net,
+};
+use neogrok_protocol::hisui::frame::AccessAction;
+
+use crate::config::access::AccessCfg;
+
+/// Maximum number of rules that user can specify for the
+/// single server
+pub const MAX_USER_ACCESS_RULES: usize = 64;
+
+/// Length of the `::ffff:0:0/96` prefix of the IPv4-mapped
+/// addresses
+const MAPPED_PREFIX_LEN: u8 = 96;
+
+/// IPv4-mapped networks are turned into the IPv4 ones, as
+/// the checked addresses are canonicalized too
+fn canonical(network: IpNet) -> IpNet {
+    let IpNet::V6(v6) = network else {
+        return network;
+    };
+
+    match v6.network().to_ipv4_mapped() {
+        Some(address) if v6.prefix_len() >= MAPPED_PREFIX_LEN => {
+            Ipv4Net::new(address, v6.prefix_len() - MAPPED_PREFIX_LEN)
+                .map_or(network, IpNet::V4)
+        }
+        _ => network,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessList {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+/// Access rules checked by the proxy listener right after
+/// `accept()`. Address should be permitted by both
+/// server-wide and user-specified rules.
+#[derive(Debug, Clone, Default)]
+pub struct AccessFilter {
+    server: AccessList,
+    user: AccessList,
+}
+
+impl AccessList {
+    /// Denied networks take precedence over the allowed
+    /// ones, empty allow list permits everything that
+    /// is not denied.
+    pub fn permits(&self, address: IpAddr) -> bool {
+        let address = address.to_canonical();
+        if self.deny.iter().any(|net| net.contains(&address)) {
+            return false;
+        }
+
+        self.allow.is_empty()
+            || self
+                .allow
+                .iter()
+                .any(|net| net.contains(&address))
+    }
+
+    pub fn push(&mut self, action: AccessAction, network: IpNet) {
+        let network = canonical(network);
+        match action {
+            AccessAction::Allow => self.allow.push(network),
+            AccessAction::Deny => self.deny.push(network),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.allow.len() + self.deny.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn from_cfg(cfg: &AccessCfg) -> Self {
+        Self {
+            allow: cfg.allow.iter().copied().map(canonical).collect(),
+            deny: cfg.deny.iter().copied().map(canonical).collect(),
+        }
+    }
+}
+
+impl AccessFilter {
+    pub fn permits(&self, address: IpAddr) -> bool {
+        self.server.permits(address) && self.user.permits(address)
+    }
+
+    pub fn new(server: AccessList, user: AccessList) -> Self {
+        Self { server, user }
+    }
+}
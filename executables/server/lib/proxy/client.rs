@@ -1,37 +1,99 @@
+use std::{
+    sync::Arc,
+    time::Duration,
+};
+
 use flume::{
     Receiver,
     Sender,
 };
+use neogrok_protocol::{
+    buffers::BufferPool,
+    protocol::error::ProtocolError,
+};
 use tokio::{
-    io::{
-        AsyncReadExt,
-        AsyncWriteExt,
-    },
+    io::AsyncWriteExt,
     net::TcpStream,
+    time::Instant,
 };
 
-use crate::commands::{
-    MasterCommand,
-    SlaveCommand,
+use crate::{
+    commands::{
+        MasterCommand,
+        SlaveCommand,
+    },
+    hisui::state::ClientId,
+    proxy::reports::ErrorReports,
+    quota::UserQuota,
+    shaping::Shaper,
+    utils::idle_deadline,
 };
 
+/// Restrictions applied to the single public client
+#[derive(Debug)]
+pub struct ClientLimits {
+    pub quota: Arc<UserQuota>,
+    pub shaper: Shaper,
+
+    /// Client is disconnected if there was no traffic in
+    /// both directions for this long
+    pub idle_timeout: Option<Duration>,
+
+    /// Shared by all clients of the tunnel
+    pub reports: Arc<ErrorReports>,
+}
+
+impl ClientLimits {
+    /// Accounts and shapes `bytes` transferred in either
+    /// direction, returns `false` if the client must be
+    /// disconnected
+    async fn transfer(
+        &self,
+        master: &Sender<MasterCommand>,
+        bytes: usize,
+    ) -> bool {
+        if !self.quota.consume_traffic(bytes) {
+            tracing::warn!("traffic quota exceeded, disconnecting");
+            self.reports
+                .report(master, ProtocolError::TrafficQuotaExceeded)
+                .await;
+            return false;
+        }
+        self.shaper.consume(bytes).await;
+
+        true
+    }
+}
+
 pub async fn run_tcp_client(
     mut stream: TcpStream,
     master: Sender<MasterCommand>,
     self_rx: Receiver<SlaveCommand>,
+    limits: ClientLimits,
 
-    id: u16,
+    id: ClientId,
     per_client_size: usize,
 ) {
-    let mut buffer = vec![0; per_client_size];
+    let mut buffers = BufferPool::new(per_client_size);
     let mut forcibly_disconnected = false;
+    let mut last_activity = Instant::now();
 
     loop {
         tokio::select! {
-            read = stream.read(&mut buffer) => {
+            _ = idle_deadline(last_activity, limits.idle_timeout) => {
+                tracing::info!("client is idle for too long, disconnecting");
+                break;
+            }
+
+            read = buffers.read_from(&mut stream) => {
                 let Ok(read @ 1..) = read else { break };
+                last_activity = Instant::now();
+                if !limits.transfer(&master, read).await {
+                    break;
+                }
+
                 let Ok(_) = master.send_async(
-                    MasterCommand::Forward { id, buffer: Vec::from(&buffer[..read]) }
+                    MasterCommand::Forward { id, buffer: buffers.split() }
                 ).await else {
                     break;
                 };
@@ -46,6 +108,11 @@ pub async fn run_tcp_client(
                     }
 
                     SlaveCommand::Forward { buffer } => {
+                        last_activity = Instant::now();
+                        if !limits.transfer(&master, buffer.len()).await {
+                            break;
+                        }
+
                         let Ok(_) = stream.write_all(&buffer).await else {
                             break;
                         };
@@ -1,38 +1,81 @@
 use std::{
-    net::SocketAddr,
     sync::Arc,
+    time::Duration,
 };
 
 use flume::Sender;
-use idpool::prelude::FlatIdPool;
+use neogrok_protocol::{
+    hisui::frame::ConnectMetadata,
+    protocol::error::ProtocolError,
+};
 use tokio::{
     net::TcpListener,
-    sync::{
-        oneshot,
-        Mutex,
-    },
+    sync::oneshot,
 };
+use tracing::Instrument;
 
 use crate::{
     commands::{
         MasterCommand,
         ShutdownToken,
     },
-    proxy::client::run_tcp_client,
+    config::shaping::RateCfg,
+    hisui::state::ClientIdPool,
+    proxy::{
+        access::AccessFilter,
+        client::{
+            run_tcp_client,
+            ClientLimits,
+        },
+        rejected::{
+            RejectReason,
+            RejectedClients,
+        },
+        reports::ErrorReports,
+    },
+    quota::UserQuota,
+    shaping::Shaper,
+    utils::{
+        set_tcp_keepalive,
+        unix_timestamp_millis,
+    },
 };
 
+/// Restrictions applied to the public clients of the tunnel
+#[derive(Debug)]
+pub struct TunnelPolicy {
+    pub access: AccessFilter,
+    pub quota: Arc<UserQuota>,
+
+    /// Shared by all clients of the tunnel
+    pub shaper: Shaper,
+    pub client_rate: Option<RateCfg>,
+
+    pub client_idle: Option<Duration>,
+    pub tcp_keepalive: Option<Duration>,
+
+    /// Server-wide counters exposed as metrics
+    pub rejected: Arc<RejectedClients>,
+}
+
 pub async fn run_tcp_listener(
     listener: TcpListener,
-    creator: SocketAddr,
 
-    pool: Arc<Mutex<FlatIdPool<u16>>>,
+    pool: Arc<ClientIdPool>,
 
     master: Sender<MasterCommand>,
     mut token: oneshot::Receiver<ShutdownToken>,
 
+    policy: TunnelPolicy,
     per_client_size: usize,
 ) {
+    let listener_port = listener
+        .local_addr()
+        .map(|addr| addr.port())
+        .unwrap_or_default();
     let mut by_error = false;
+    let mut rejected: u64 = 0;
+    let reports = Arc::new(ErrorReports::default());
     loop {
         tokio::select! {
             biased;
@@ -47,16 +90,79 @@ pub async fn run_tcp_listener(
                     break;
                 };
 
-                let id = pool.lock().await.request_id();
+                if !policy.access.permits(address.ip()) {
+                    rejected += 1;
+                    policy.rejected.record(RejectReason::Access);
+                    tracing::warn!(
+                        peer = %address,
+                        rejected,
+                        "client rejected by access rules"
+                    );
+                    continue;
+                }
+
+                let permit = if policy.quota.traffic_exceeded() {
+                    Err(ProtocolError::TrafficQuotaExceeded)
+                } else {
+                    policy.quota
+                        .try_acquire_client()
+                        .ok_or(ProtocolError::ClientQuotaExceeded)
+                };
+                let permit = match permit {
+                    Ok(permit) => permit,
+                    Err(error) => {
+                        rejected += 1;
+                        policy.rejected.record(RejectReason::Quota);
+                        tracing::warn!(
+                            peer = %address,
+                            rejected,
+                            %error,
+                            "client rejected by quota"
+                        );
+
+                        if !reports.report(&master, error).await {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+
+                if let Some(idle) = policy.tcp_keepalive {
+                    if let Err(error) = set_tcp_keepalive(&stream, idle) {
+                        tracing::warn!(peer = %address, %error, "failed to set TCP keepalive");
+                    }
+                }
+
+                let Ok(id) = pool.request_id() else {
+                    // Every id is either in use or quarantined
+                    rejected += 1;
+                    policy.rejected.record(RejectReason::Ids);
+                    let error = ProtocolError::ClientIdsExhausted;
+                    tracing::warn!(
+                        peer = %address,
+                        rejected,
+                        %error,
+                        "client rejected, no free ids"
+                    );
+
+                    if !reports.report(&master, error).await {
+                        break;
+                    }
+                    continue;
+                };
                 tracing::info!(
-                    ?address,
-                    ?creator,
-                    ?id,
+                    client = id.id,
+                    peer = %address,
                     "client connected"
                 );
 
+                let metadata = ConnectMetadata {
+                    address,
+                    listener_port,
+                    timestamp: unix_timestamp_millis(),
+                };
                 let (tx, rx) = flume::unbounded();
-                let Ok(()) = master.send_async(MasterCommand::Connected { id, tx }).await else {
+                let Ok(()) = master.send_async(MasterCommand::Connected { id, tx, metadata }).await else {
                     // state is dropped, so there is no sense in sending
                     // Closed to the master nor reporting in trace
                     break;
@@ -64,24 +170,44 @@ pub async fn run_tcp_listener(
 
                 let master = Sender::clone(&master);
                 let pool = Arc::clone(&pool);
+                let limits = ClientLimits {
+                    quota: Arc::clone(&policy.quota),
+                    shaper: policy.shaper.with_rate(policy.client_rate),
+                    idle_timeout: policy.client_idle,
+                    reports: Arc::clone(&reports),
+                };
+
+                let span = tracing::info_span!(
+                    "client",
+                    client = id.id,
+                    peer = %address
+                );
+
                 tokio::spawn(async move {
                     run_tcp_client(
                         stream,
                         master,
                         rx,
+                        limits,
                         id,
                         per_client_size,
                     )
                     .await;
 
-                    pool.lock()
-                        .await
-                        .return_id(id);
-                });
+                    drop(permit);
+                    pool.return_id(id);
+                }.instrument(span));
             }
         }
     }
 
+    if rejected != 0 {
+        tracing::info!(
+            rejected,
+            "listener closed, some clients were rejected"
+        );
+    }
+
     if by_error {
         master
             .send_async(MasterCommand::Closed)
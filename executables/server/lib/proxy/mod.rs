@@ -1,2 +1,8 @@
+pub mod access;
 pub mod client;
 pub mod listener;
+pub mod rejected;
+pub mod reports;
+
+#[cfg(test)]
+mod tests;
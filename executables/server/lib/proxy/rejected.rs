@@ -0,0 +1,55 @@
+use std::sync::atomic::{
+    AtomicU64,
+    Ordering,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// Denied by the server or user access rules
+    Access,
+
+    /// Clients or traffic quota of the owner is exceeded
+    Quota,
+
+    /// Every client id is either in use or quarantined
+    Ids,
+}
+
+/// Public clients rejected by all tunnels since the start
+#[derive(Debug, Default)]
+pub struct RejectedClients {
+    access: AtomicU64,
+    quota: AtomicU64,
+    ids: AtomicU64,
+}
+
+impl RejectReason {
+    pub const ALL: [Self; 3] = [Self::Access, Self::Quota, Self::Ids];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Access => "access",
+            Self::Quota => "quota",
+            Self::Ids => "ids",
+        }
+    }
+}
+
+impl RejectedClients {
+    pub fn record(&self, reason: RejectReason) {
+        self.counter(reason)
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, reason: RejectReason) -> u64 {
+        self.counter(reason).load(Ordering::Relaxed)
+    }
+
+    fn counter(&self, reason: RejectReason) -> &AtomicU64 {
+        match reason {
+            RejectReason::Access => &self.access,
+            RejectReason::Quota => &self.quota,
+            RejectReason::Ids => &self.ids,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::atomic::{
+    AtomicU64,
+    Ordering,
+};
+
+use flume::Sender;
+use neogrok_protocol::protocol::error::ProtocolError;
+
+use crate::commands::MasterCommand;
+
+/// Errors of the tunnel reported to its owner. Every kind
+/// is reported once, repeated ones are only logged, so a
+/// flood of the rejected clients doesn't flood the control
+/// session
+#[derive(Debug, Default)]
+pub struct ErrorReports {
+    /// Bit per error code, all of them are below 64
+    reported: AtomicU64,
+}
+
+impl ErrorReports {
+    /// Returns `false` if the session is closed
+    pub async fn report(
+        &self,
+        master: &Sender<MasterCommand>,
+        error: ProtocolError,
+    ) -> bool {
+        let bit = 1_u64 << (error as u8);
+        if self.reported.fetch_or(bit, Ordering::AcqRel) & bit != 0 {
+            tracing::debug!(%error, "error was already reported");
+            return true;
+        }
+
+        master
+            .send_async(MasterCommand::Error { error })
+            .await
+            .is_ok()
+    }
+}
@@ -0,0 +1,94 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use neogrok_protocol::hisui::frame::AccessAction;
+
+use crate::{
+    config::access::AccessCfg,
+    proxy::access::*,
+};
+
+fn ip(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+fn access_list(rules: &[(AccessAction, &str)]) -> AccessList {
+    let mut list = AccessList::default();
+    for &(action, network) in rules {
+        list.push(action, network.parse::<IpNet>().unwrap());
+    }
+
+    list
+}
+
+#[test]
+fn test_empty_access_list() {
+    let list = AccessList::default();
+
+    assert!(list.is_empty());
+    assert!(list.permits(ip("10.1.2.3")));
+    assert!(list.permits(ip("2001:db8::1")));
+}
+
+#[test]
+fn test_access_list_deny_precedence() {
+    let list = access_list(&[
+        (AccessAction::Allow, "10.0.0.0/8"),
+        (AccessAction::Deny, "10.1.0.0/16"),
+    ]);
+
+    assert_eq!(list.len(), 2);
+    assert!(list.permits(ip("10.2.3.4")));
+    assert!(!list.permits(ip("10.1.2.3")));
+
+    // Non-empty allow list permits only its networks
+    assert!(!list.permits(ip("192.168.1.1")));
+
+    // Deny wins even if the same network is allowed
+    let list = access_list(&[
+        (AccessAction::Allow, "10.1.0.0/16"),
+        (AccessAction::Deny, "10.1.0.0/16"),
+    ]);
+    assert!(!list.permits(ip("10.1.2.3")));
+}
+
+#[test]
+fn test_access_list_mapped_addresses() {
+    let list = access_list(&[(AccessAction::Deny, "10.1.0.0/16")]);
+
+    assert!(!list.permits(ip("::ffff:10.1.2.3")));
+    assert!(list.permits(ip("::ffff:10.2.3.4")));
+}
+
+#[test]
+fn test_access_list_mapped_networks() {
+    let list = access_list(&[
+        (AccessAction::Allow, "::ffff:10.0.0.0/104"),
+        (AccessAction::Deny, "::ffff:10.1.0.0/112"),
+    ]);
+
+    assert!(list.permits(ip("10.2.3.4")));
+    assert!(list.permits(ip("::ffff:10.2.3.4")));
+    assert!(!list.permits(ip("10.1.2.3")));
+    assert!(!list.permits(ip("::ffff:10.1.2.3")));
+    assert!(!list.permits(ip("192.168.1.1")));
+
+    let cfg = AccessCfg {
+        allow: vec![],
+        deny: vec!["::ffff:10.1.0.0/112".parse().unwrap()],
+    };
+    assert!(!AccessList::from_cfg(&cfg).permits(ip("10.1.2.3")));
+}
+
+#[test]
+fn test_access_filter() {
+    let server = access_list(&[(AccessAction::Deny, "10.1.0.0/16")]);
+    let user = access_list(&[(AccessAction::Allow, "10.0.0.0/8")]);
+    let filter = AccessFilter::new(server, user);
+
+    assert!(filter.permits(ip("10.2.3.4")));
+
+    // User can't allow what the server denies
+    assert!(!filter.permits(ip("10.1.2.3")));
+    assert!(!filter.permits(ip("192.168.1.1")));
+}
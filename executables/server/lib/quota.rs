@@ -0,0 +1,242 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+        Mutex,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::config::{
+    permissions::PermissionGroup,
+    quotas::{
+        QuotaEntry,
+        QuotasCfg,
+    },
+};
+
+#[derive(Debug)]
+struct TrafficWindow {
+    started: Instant,
+    used: u64,
+}
+
+/// Usage counters of the single user, limited by the
+/// config of its permission group
+#[derive(Debug)]
+pub struct UserQuota {
+    limits: QuotaEntry,
+
+    tunnels: AtomicUsize,
+    clients: AtomicUsize,
+    traffic: Mutex<TrafficWindow>,
+}
+
+/// Quota config of the permission group and counters of its
+/// users, identified by the address
+#[derive(Debug)]
+struct GroupQuotas {
+    limits: QuotaEntry,
+    users: Mutex<FxHashMap<IpAddr, Arc<UserQuota>>>,
+}
+
+/// Quotas of the permission groups. Counters are shared by
+/// all sessions of the user and survive reconnects.
+#[derive(Debug)]
+pub struct Quotas {
+    base: GroupQuotas,
+    magic: GroupQuotas,
+}
+
+/// Occupied tunnel slot, released on drop
+#[derive(Debug)]
+pub struct TunnelPermit {
+    quota: Arc<UserQuota>,
+}
+
+/// Occupied public client slot, released on drop
+#[derive(Debug)]
+pub struct ClientPermit {
+    quota: Arc<UserQuota>,
+}
+
+/// Increments `counter` unless it already reached `max`
+fn try_increment(counter: &AtomicUsize, max: Option<usize>) -> bool {
+    let max = max.unwrap_or(usize::MAX);
+    counter
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            (n < max).then_some(n + 1)
+        })
+        .is_ok()
+}
+
+impl UserQuota {
+    /// Returns `None` if the user already reached its
+    /// tunnels limit
+    pub fn try_acquire_tunnel(self: &Arc<Self>) -> Option<TunnelPermit> {
+        try_increment(&self.tunnels, self.limits.max_tunnels).then(|| {
+            TunnelPermit {
+                quota: Arc::clone(self),
+            }
+        })
+    }
+
+    /// Returns `None` if the user already reached its
+    /// clients limit, counted over all of its tunnels
+    pub fn try_acquire_client(self: &Arc<Self>) -> Option<ClientPermit> {
+        try_increment(&self.clients, self.limits.max_clients).then(|| {
+            ClientPermit {
+                quota: Arc::clone(self),
+            }
+        })
+    }
+
+    /// Accounts transferred bytes, returns `false` if the
+    /// traffic quota is exceeded
+    pub fn consume_traffic(&self, bytes: usize) -> bool {
+        self.consume_traffic_at(bytes, Instant::now())
+    }
+
+    pub fn consume_traffic_at(&self, bytes: usize, now: Instant) -> bool {
+        let Some(quota) = self.limits.traffic else {
+            return true;
+        };
+        let mut window = self.traffic.lock().unwrap();
+
+        if now.saturating_duration_since(window.started)
+            >= Duration::from_secs(quota.period)
+        {
+            window.started = now;
+            window.used = 0;
+        }
+
+        window.used = window.used.saturating_add(bytes as u64);
+        window.used <= quota.limit
+    }
+
+    /// Checks whether the traffic quota is exceeded without
+    /// consuming anything
+    pub fn traffic_exceeded(&self) -> bool {
+        !self.consume_traffic(0)
+    }
+
+    /// Whether forgetting these counters loses nothing: no
+    /// slots are taken and the traffic period is over
+    fn is_unused(&self, now: Instant) -> bool {
+        if self.tunnels.load(Ordering::Acquire) != 0
+            || self.clients.load(Ordering::Acquire) != 0
+        {
+            return false;
+        }
+
+        self.limits.traffic.is_none_or(|quota| {
+            let window = self.traffic.lock().unwrap();
+            now.saturating_duration_since(window.started)
+                >= Duration::from_secs(quota.period)
+        })
+    }
+
+    pub fn new(limits: QuotaEntry) -> Self {
+        Self::new_at(limits, Instant::now())
+    }
+
+    pub fn new_at(limits: QuotaEntry, now: Instant) -> Self {
+        Self {
+            limits,
+            tunnels: AtomicUsize::new(0),
+            clients: AtomicUsize::new(0),
+            traffic: Mutex::new(TrafficWindow {
+                started: now,
+                used: 0,
+            }),
+        }
+    }
+}
+
+impl TunnelPermit {
+    pub fn quota(&self) -> &Arc<UserQuota> {
+        &self.quota
+    }
+}
+
+impl Drop for TunnelPermit {
+    fn drop(&mut self) {
+        self.quota.tunnels.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Drop for ClientPermit {
+    fn drop(&mut self) {
+        self.quota.clients.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl GroupQuotas {
+    fn user_at(&self, address: IpAddr, now: Instant) -> Arc<UserQuota> {
+        let mut users = self.users.lock().unwrap();
+        let address = address.to_canonical();
+        if let Some(quota) = users.get(&address) {
+            return Arc::clone(quota);
+        }
+
+        // Forget users which have nothing to remember, so the
+        // map doesn't grow with every address seen
+        users.retain(|_, quota| {
+            Arc::strong_count(quota) > 1 || !quota.is_unused(now)
+        });
+
+        let quota = Arc::new(UserQuota::new_at(self.limits.clone(), now));
+        users.insert(address, Arc::clone(&quota));
+
+        quota
+    }
+
+    fn new(limits: &QuotaEntry) -> Self {
+        Self {
+            limits: limits.clone(),
+            users: Mutex::default(),
+        }
+    }
+}
+
+impl Quotas {
+    /// Counters of the user of the `group` connected from
+    /// the `address`, shared with its other sessions
+    pub fn user(
+        &self,
+        group: PermissionGroup,
+        address: IpAddr,
+    ) -> Arc<UserQuota> {
+        self.user_at(group, address, Instant::now())
+    }
+
+    pub fn user_at(
+        &self,
+        group: PermissionGroup,
+        address: IpAddr,
+        now: Instant,
+    ) -> Arc<UserQuota> {
+        let group = match group {
+            PermissionGroup::Base => &self.base,
+            PermissionGroup::Magic => &self.magic,
+        };
+
+        group.user_at(address, now)
+    }
+
+    pub fn new(cfg: &QuotasCfg) -> Self {
+        Self {
+            base: GroupQuotas::new(&cfg.base),
+            magic: GroupQuotas::new(&cfg.magic),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+
+use tokio::time::{
+    interval,
+    Interval,
+    MissedTickBehavior,
+};
+
+use crate::{
+    config::{
+        error::ConfigLoadError,
+        overrides::ConfigSource,
+    },
+    context::Context,
+    infinite_future::infinite_future,
+};
+
+#[cfg(unix)]
+type Hangup = tokio::signal::unix::Signal;
+
+// No SIGHUP outside of unix
+#[cfg(not(unix))]
+type Hangup = std::convert::Infallible;
+
+/// Config reload trigger: SIGHUP or config file
+/// modification, if watching is enabled
+pub struct ReloadTrigger {
+    source: ConfigSource,
+
+    hangup: Option<Hangup>,
+    watch: Option<Interval>,
+    modified: Option<SystemTime>,
+}
+
+impl ReloadTrigger {
+    /// Resolves when config should be reloaded
+    pub async fn triggered(&mut self) {
+        tokio::select! {
+            _ = wait_hangup(&mut self.hangup) => {}
+            _ = wait_modified(
+                &self.source.path,
+                &mut self.watch,
+                &mut self.modified,
+            ) => {}
+        }
+    }
+
+    pub fn source(&self) -> &ConfigSource {
+        &self.source
+    }
+
+    pub fn new(
+        source: ConfigSource,
+        watch_interval: Option<Duration>,
+    ) -> Self {
+        let modified = std::fs::metadata(&source.path)
+            .and_then(|m| m.modified())
+            .ok();
+
+        Self {
+            source,
+            hangup: listen_hangup(),
+            watch: watch_interval.map(|period| {
+                let mut watch = interval(period);
+                watch.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                watch
+            }),
+            modified,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn listen_hangup() -> Option<Hangup> {
+    use tokio::signal::unix::{
+        signal,
+        SignalKind,
+    };
+
+    signal(SignalKind::hangup())
+        .map_err(|error| {
+            tracing::error!(%error, "failed to listen for SIGHUP");
+        })
+        .ok()
+}
+
+#[cfg(not(unix))]
+fn listen_hangup() -> Option<Hangup> {
+    None
+}
+
+async fn wait_hangup(hangup: &mut Option<Hangup>) {
+    #[cfg(unix)]
+    if let Some(signal) = hangup {
+        if signal.recv().await.is_some() {
+            return;
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = hangup;
+
+    infinite_future().await;
+}
+
+async fn wait_modified(
+    path: &Path,
+    watch: &mut Option<Interval>,
+    last_modified: &mut Option<SystemTime>,
+) {
+    let Some(watch) = watch else {
+        infinite_future().await;
+        return;
+    };
+
+    loop {
+        watch.tick().await;
+        // Single stat call, not worth spawning blocking task for it
+        let Ok(modified) =
+            std::fs::metadata(path).and_then(|m| m.modified())
+        else {
+            continue;
+        };
+
+        if last_modified.replace(modified) != Some(modified) {
+            break;
+        }
+    }
+}
+
+/// Loads config from the `source` and makes it active for
+/// the new sessions. Old config is kept if the new one is
+/// invalid.
+pub fn reload_config(
+    source: &ConfigSource,
+    context: &Context,
+) -> Result<(), ConfigLoadError> {
+    let new = source.load()?;
+    let old = context.config();
+
+    if new.server.listen != old.server.listen {
+        tracing::warn!("server.listen change requires restart");
+    }
+    if new.runtime.workers != old.runtime.workers {
+        tracing::warn!("runtime.workers change requires restart");
+    }
+    if new.compression.offload != old.compression.offload {
+        tracing::warn!("compression.offload change requires restart");
+    }
+    if new.quotas != old.quotas {
+        tracing::warn!("quotas change requires restart");
+    }
+    if new.shaping != old.shaping {
+        tracing::warn!("shaping change requires restart");
+    }
+    if new.logging != old.logging {
+        tracing::warn!("logging change requires restart");
+    }
+    if new.audit != old.audit {
+        tracing::warn!("audit change requires restart");
+    }
+    if new.metrics != old.metrics {
+        tracing::warn!("metrics change requires restart");
+    }
+
+    context.replace_config(Arc::new(new));
+    Ok(())
+}
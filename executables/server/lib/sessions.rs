@@ -0,0 +1,89 @@
+use std::{
+    net::SocketAddr,
+    sync::Mutex,
+    time::Duration,
+};
+
+use neogrok_protocol::{
+    compression::adaptive::AdaptiveStats,
+    hisui::rtt::RttStats,
+};
+use rustc_hash::FxHashMap;
+
+use crate::user::Peer;
+
+#[derive(Debug, Clone)]
+pub struct SessionMetrics {
+    pub address: SocketAddr,
+    pub rtt: RttStats,
+    pub compression: AdaptiveStats,
+}
+
+/// Per-session statistics of the connected users
+#[derive(Debug, Default)]
+pub struct Sessions {
+    metrics: Mutex<FxHashMap<u64, SessionMetrics>>,
+}
+
+/// Removes session from the registry on drop
+#[derive(Debug)]
+pub struct SessionGuard<'a> {
+    sessions: &'a Sessions,
+    session: u64,
+}
+
+impl Sessions {
+    pub fn register(&self, peer: &Peer) -> SessionGuard<'_> {
+        self.lock().insert(
+            peer.session,
+            SessionMetrics {
+                address: peer.address,
+                rtt: RttStats::default(),
+                compression: AdaptiveStats::default(),
+            },
+        );
+
+        SessionGuard {
+            sessions: self,
+            session: peer.session,
+        }
+    }
+
+    pub fn record_rtt(&self, session: u64, rtt: Duration) {
+        if let Some(metrics) = self.lock().get_mut(&session) {
+            metrics.rtt.record(rtt);
+        }
+    }
+
+    pub fn record_compression(&self, session: u64, stats: AdaptiveStats) {
+        if let Some(metrics) = self.lock().get_mut(&session) {
+            metrics.compression = stats;
+        }
+    }
+
+    /// Copy of the metrics sorted by session id
+    pub fn snapshot(&self) -> Vec<(u64, SessionMetrics)> {
+        let mut snapshot: Vec<_> = self
+            .lock()
+            .iter()
+            .map(|(&session, metrics)| (session, metrics.clone()))
+            .collect();
+        snapshot.sort_unstable_by_key(|&(session, _)| session);
+
+        snapshot
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, FxHashMap<u64, SessionMetrics>> {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        self.sessions.lock().remove(&self.session);
+    }
+}
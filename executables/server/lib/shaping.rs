@@ -0,0 +1,178 @@
+use std::{
+    sync::{
+        Arc,
+        Mutex,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::config::{
+    permissions::PermissionGroup,
+    shaping::{
+        GroupShapingCfg,
+        RateCfg,
+        ShapingCfg,
+    },
+};
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+
+    state: Mutex<BucketState>,
+}
+
+/// Chain of the token buckets, data passes through the
+/// shaper only when all buckets have enough tokens
+#[derive(Debug, Clone, Default)]
+pub struct Shaper {
+    buckets: Vec<Arc<TokenBucket>>,
+}
+
+#[derive(Debug)]
+struct GroupShaping {
+    cfg: GroupShapingCfg,
+    bucket: Option<Arc<TokenBucket>>,
+}
+
+/// Server-wide shaping state, shared between all sessions
+#[derive(Debug)]
+pub struct Shaping {
+    server: Option<Arc<TokenBucket>>,
+
+    base: GroupShaping,
+    magic: GroupShaping,
+}
+
+impl TokenBucket {
+    /// Takes `bytes` tokens from the bucket going into debt
+    /// if there are not enough of them. Returns time
+    /// needed to repay the debt.
+    pub fn reserve(&self, bytes: usize) -> Duration {
+        self.reserve_at(bytes, Instant::now())
+    }
+
+    pub fn reserve_at(&self, bytes: usize, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now
+            .saturating_duration_since(state.updated)
+            .as_secs_f64();
+
+        state.tokens =
+            (state.tokens + elapsed * self.rate).min(self.burst);
+        state.updated = state.updated.max(now);
+        state.tokens -= bytes as f64;
+
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        }
+    }
+
+    pub fn new(cfg: RateCfg) -> Self {
+        let rate = cfg.rate.get() as f64;
+        let burst = cfg.burst.map_or(rate, |burst| burst.get() as f64);
+
+        Self {
+            rate,
+            burst,
+            state: Mutex::new(BucketState {
+                tokens: burst,
+                updated: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl Shaper {
+    /// Waits until `bytes` can be transferred
+    pub async fn consume(&self, bytes: usize) {
+        let wait = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.reserve(bytes))
+            .max()
+            .unwrap_or_default();
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Creates new shaper with additional bucket, if rate
+    /// is specified
+    pub fn with_rate(&self, rate: Option<RateCfg>) -> Self {
+        let mut buckets = self.buckets.clone();
+        buckets.extend(rate.map(|rate| Arc::new(TokenBucket::new(rate))));
+
+        Self { buckets }
+    }
+}
+
+impl GroupShaping {
+    fn new(cfg: &GroupShapingCfg) -> Self {
+        Self {
+            cfg: cfg.clone(),
+            bucket: cfg
+                .group
+                .map(|rate| Arc::new(TokenBucket::new(rate))),
+        }
+    }
+}
+
+impl Shaping {
+    /// Creates shaper for the new user: server-wide, group
+    /// and user buckets
+    pub fn user_shaper(&self, group: PermissionGroup) -> Shaper {
+        let group = self.group(group);
+        let buckets = self
+            .server
+            .iter()
+            .chain(group.bucket.iter())
+            .cloned()
+            .collect();
+
+        Shaper { buckets }.with_rate(group.cfg.user)
+    }
+
+    /// Adds tunnel bucket to the shaper of its owner
+    pub fn tunnel_shaper(
+        &self,
+        user: &Shaper,
+        group: PermissionGroup,
+    ) -> Shaper {
+        user.with_rate(self.group(group).cfg.tunnel)
+    }
+
+    pub fn client_rate(&self, group: PermissionGroup) -> Option<RateCfg> {
+        self.group(group).cfg.client
+    }
+
+    fn group(&self, group: PermissionGroup) -> &GroupShaping {
+        match group {
+            PermissionGroup::Base => &self.base,
+            PermissionGroup::Magic => &self.magic,
+        }
+    }
+
+    pub fn new(cfg: &ShapingCfg) -> Self {
+        Self {
+            server: cfg
+                .server
+                .map(|rate| Arc::new(TokenBucket::new(rate))),
+            base: GroupShaping::new(&cfg.base),
+            magic: GroupShaping::new(&cfg.magic),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::io;
+
+use tokio::sync::watch;
+
+use crate::infinite_future::infinite_future;
+
+/// Graceful shutdown coordinator. Every session holds a
+/// [`ShutdownListener`], server is drained when all of them
+/// are dropped.
+#[derive(Debug)]
+pub struct Shutdown {
+    tx: watch::Sender<bool>,
+}
+
+#[derive(Debug)]
+pub struct ShutdownListener {
+    rx: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    pub fn subscribe(&self) -> ShutdownListener {
+        ShutdownListener {
+            rx: self.tx.subscribe(),
+        }
+    }
+
+    pub fn initiate(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_initiated(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Waits until all listeners are dropped
+    pub async fn drained(&self) {
+        self.tx.closed().await
+    }
+
+    pub fn sessions(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            tx: watch::channel(false).0,
+        }
+    }
+}
+
+impl ShutdownListener {
+    /// Resolves once the shutdown is initiated
+    pub async fn initiated(&mut self) {
+        while !*self.rx.borrow_and_update() {
+            if self.rx.changed().await.is_err() {
+                infinite_future().await;
+            }
+        }
+    }
+}
+
+/// Waits for the SIGTERM or SIGINT, never resolves if
+/// signal handlers can't be installed
+pub async fn shutdown_signal() {
+    if let Err(error) = wait_signal().await {
+        tracing::error!(%error, "failed to listen for shutdown signals");
+        infinite_future().await;
+    }
+}
+
+#[cfg(unix)]
+async fn wait_signal() -> io::Result<()> {
+    use tokio::signal::unix::{
+        signal,
+        SignalKind,
+    };
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = interrupt.recv() => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn wait_signal() -> io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
@@ -0,0 +1,190 @@
+use std::{
+    net::IpAddr,
+    num::NonZeroU64,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::{
+    config::{
+        permissions::PermissionGroup,
+        quotas::{
+            QuotaEntry,
+            QuotasCfg,
+            TrafficQuotaCfg,
+        },
+        shaping::RateCfg,
+    },
+    quota::*,
+    shaping::*,
+};
+
+fn ip(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+fn rate(rate: u64, burst: Option<u64>) -> RateCfg {
+    RateCfg {
+        rate: NonZeroU64::new(rate).unwrap(),
+        burst: burst.and_then(NonZeroU64::new),
+    }
+}
+
+#[test]
+fn test_tunnel_permits() {
+    let quotas = Quotas::new(&QuotasCfg {
+        base: QuotaEntry {
+            max_tunnels: Some(2),
+            ..Default::default()
+        },
+        magic: QuotaEntry::default(),
+    });
+    let user = quotas.user(PermissionGroup::Base, ip("10.0.0.1"));
+
+    let first = user.try_acquire_tunnel().unwrap();
+    let _second = user.try_acquire_tunnel().unwrap();
+    assert!(user.try_acquire_tunnel().is_none());
+
+    // Other sessions of the same user share the counters
+    let session =
+        quotas.user(PermissionGroup::Base, ip("::ffff:10.0.0.1"));
+    assert!(session.try_acquire_tunnel().is_none());
+
+    // Every user has its own counters
+    let other = quotas.user(PermissionGroup::Base, ip("10.0.0.2"));
+    assert!(other.try_acquire_tunnel().is_some());
+
+    drop(first);
+    assert!(user.try_acquire_tunnel().is_some());
+
+    let magic = quotas.user(PermissionGroup::Magic, ip("10.0.0.1"));
+    let permits = (0..16)
+        .map(|_| magic.try_acquire_tunnel())
+        .collect::<Option<Vec<_>>>();
+    assert!(permits.is_some());
+}
+
+#[test]
+fn test_client_permits() {
+    let quotas = Quotas::new(&QuotasCfg {
+        base: QuotaEntry {
+            max_clients: Some(1),
+            ..Default::default()
+        },
+        magic: QuotaEntry::default(),
+    });
+    let user = quotas.user(PermissionGroup::Base, ip("10.0.0.1"));
+
+    let client = user.try_acquire_client().unwrap();
+    assert!(user.try_acquire_client().is_none());
+
+    drop(client);
+    assert!(user.try_acquire_client().is_some());
+}
+
+#[test]
+fn test_traffic_window() {
+    let quota = UserQuota::new(QuotaEntry {
+        traffic: Some(TrafficQuotaCfg {
+            limit: 100,
+            period: 10,
+        }),
+        ..Default::default()
+    });
+    let start = Instant::now();
+
+    assert!(quota.consume_traffic_at(60, start));
+    assert!(quota.consume_traffic_at(40, start + Duration::from_secs(1)));
+    assert!(!quota.consume_traffic_at(1, start + Duration::from_secs(2)));
+    assert!(!quota.consume_traffic_at(0, start + Duration::from_secs(9)));
+
+    // Usage is reset once the period is over
+    let next = start + Duration::from_secs(10);
+    assert!(quota.consume_traffic_at(0, next));
+    assert!(quota.consume_traffic_at(100, next));
+    assert!(!quota.consume_traffic_at(1, next));
+}
+
+#[test]
+fn test_traffic_survives_reconnect() {
+    let quotas = Quotas::new(&QuotasCfg {
+        base: QuotaEntry {
+            traffic: Some(TrafficQuotaCfg {
+                limit: 100,
+                period: 10,
+            }),
+            ..Default::default()
+        },
+        magic: QuotaEntry::default(),
+    });
+    let start = Instant::now();
+
+    let session =
+        quotas.user_at(PermissionGroup::Base, ip("10.0.0.1"), start);
+    assert!(!session.consume_traffic_at(101, start));
+    drop(session);
+
+    // Another user doesn't evict counters of the exceeded
+    // period
+    let later = start + Duration::from_secs(5);
+    quotas.user_at(PermissionGroup::Base, ip("10.0.0.2"), later);
+    let session =
+        quotas.user_at(PermissionGroup::Base, ip("10.0.0.1"), later);
+    assert!(session.traffic_exceeded());
+    drop(session);
+
+    // Finished period is forgotten
+    let next = start + Duration::from_secs(10);
+    quotas.user_at(PermissionGroup::Base, ip("10.0.0.3"), next);
+    let session =
+        quotas.user_at(PermissionGroup::Base, ip("10.0.0.1"), next);
+    assert!(session.consume_traffic_at(100, next));
+}
+
+#[test]
+fn test_unlimited_traffic() {
+    let quota = UserQuota::new(QuotaEntry::default());
+
+    assert!(quota.consume_traffic(usize::MAX));
+    assert!(!quota.traffic_exceeded());
+}
+
+#[test]
+fn test_token_bucket_burst() {
+    let bucket = TokenBucket::new(rate(100, Some(200)));
+    let start = Instant::now();
+
+    assert_eq!(bucket.reserve_at(200, start), Duration::ZERO);
+
+    // Going into debt, repaid at the bucket rate
+    assert_eq!(bucket.reserve_at(50, start), Duration::from_millis(500));
+}
+
+#[test]
+fn test_token_bucket_refill() {
+    let bucket = TokenBucket::new(rate(100, Some(200)));
+    let start = Instant::now();
+
+    assert_eq!(bucket.reserve_at(250, start), Duration::from_millis(500));
+    assert_eq!(
+        bucket.reserve_at(100, start + Duration::from_millis(1500)),
+        Duration::ZERO
+    );
+
+    // Tokens don't pile up above the burst
+    let later = start + Duration::from_secs(100);
+    assert_eq!(bucket.reserve_at(200, later), Duration::ZERO);
+    assert_eq!(bucket.reserve_at(1, later), Duration::from_millis(10));
+}
+
+#[test]
+fn test_token_bucket_default_burst() {
+    let bucket = TokenBucket::new(rate(100, None));
+
+    assert_eq!(
+        bucket.reserve_at(150, Instant::now()),
+        Duration::from_millis(500)
+    );
+}
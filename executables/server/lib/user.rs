@@ -1,20 +1,84 @@
-use neogrok_protocol::protocol::types::Rights;
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use neogrok_protocol::protocol::types::{
+    Capabilities,
+    Rights,
+};
+
+use crate::{
+    config::permissions::PermissionGroup,
+    proxy::access::AccessList,
+    quota::UserQuota,
+    shaping::Shaper,
+};
+
+/// Identifies the control connection
+#[derive(Debug, Clone, Copy)]
+pub struct Peer {
+    pub session: u64,
+    pub address: SocketAddr,
+}
 
 #[derive(Debug)]
 pub struct User {
+    pub group: PermissionGroup,
     pub rights: Rights,
+    pub capabilities: Capabilities,
+
+    /// Negotiated compression dictionary is used
+    pub dictionary: bool,
+
+    /// Access rules for the servers created by this user
+    pub access: AccessList,
+
+    /// Shared by all sessions of the user, replaced when
+    /// the group changes
+    pub quota: Arc<UserQuota>,
+    pub shaper: Shaper,
 }
 
 impl User {
-    pub fn new(rights: Rights) -> Self {
-        Self { rights }
+    /// Whether streaming compression and the dictionary are
+    /// used, compression is replaced when these change
+    pub fn negotiated_compression(&self) -> (bool, bool) {
+        (
+            self.capabilities
+                .contains(Capabilities::STREAM_COMPRESSION),
+            self.dictionary,
+        )
+    }
+
+    pub fn new(
+        group: PermissionGroup,
+        rights: Rights,
+        quota: Arc<UserQuota>,
+        shaper: Shaper,
+    ) -> Self {
+        Self {
+            group,
+            rights,
+            capabilities: Capabilities::empty(),
+            dictionary: false,
+            access: AccessList::default(),
+            quota,
+            shaper,
+        }
     }
 }
 
 impl Default for User {
     fn default() -> Self {
         User {
+            group: PermissionGroup::Base,
             rights: Rights::empty(),
+            capabilities: Capabilities::empty(),
+            dictionary: false,
+            access: AccessList::default(),
+            quota: Arc::new(UserQuota::new(Default::default())),
+            shaper: Shaper::default(),
         }
     }
 }
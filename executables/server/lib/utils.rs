@@ -1,2 +1,50 @@
+use std::{
+    io,
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use socket2::{
+    SockRef,
+    TcpKeepalive,
+};
+use tokio::{
+    net::TcpStream,
+    time::Instant,
+};
+
+use crate::infinite_future::infinite_future;
+
 #[cold]
 pub fn cold_path() {}
+
+pub fn unix_timestamp_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Completes when `timeout` elapses since `since`, never
+/// completes without the timeout
+pub async fn idle_deadline(since: Instant, timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => tokio::time::sleep_until(since + timeout).await,
+        None => {
+            infinite_future().await;
+        }
+    }
+}
+
+/// Enables TCP keepalive probes after `idle` time without
+/// traffic
+pub fn set_tcp_keepalive(
+    stream: &TcpStream,
+    idle: Duration,
+) -> io::Result<()> {
+    SockRef::from(stream)
+        .set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use clap::{
+    Parser,
+    Subcommand,
+};
+use neogrok_protocol::compression::algorithms::zstd::dictionary::DEFAULT_DICTIONARY_SIZE;
+use tracing::Level;
+
+/// Neogrok server, self-hosted ngrok alternative
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Path to the config file, default locations are
+    /// searched if not specified
+    #[arg(short, long, env = "NEOGROK_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Override `server.listen` address
+    #[arg(short, long)]
+    pub listen: Option<String>,
+
+    /// Override `logging.level`
+    #[arg(long)]
+    pub log_level: Option<Level>,
+
+    /// Print the default config and exit
+    #[arg(long)]
+    pub print_default_config: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Run the server, default command
+    Run,
+
+    /// Load the config and report errors, if any
+    CheckConfig,
+
+    /// Train the zstd dictionary from the captured
+    /// payloads, config is not needed
+    TrainDict {
+        /// Where to write the dictionary
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Maximum size of the dictionary in bytes
+        #[arg(short, long, default_value_t = DEFAULT_DICTIONARY_SIZE)]
+        size: usize,
+
+        /// Sample files, every file is a single payload.
+        /// Directories are searched recursively.
+        #[arg(required = true)]
+        samples: Vec<PathBuf>,
+    },
+}
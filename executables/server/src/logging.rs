@@ -0,0 +1,76 @@
+use neogrok::config::logging::{
+    LogFormat,
+    LogRotation,
+    LoggingCfg,
+};
+use tracing::Level;
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{
+        RollingFileAppender,
+        Rotation,
+    },
+};
+use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter,
+    EnvFilter,
+};
+
+/// Installs global subscriber according to the `[logging]`
+/// section. `level` overrides the configured default level.
+///
+/// Returned guard flushes file logs on drop, so it must be
+/// kept alive until exit.
+pub fn init(
+    cfg: &LoggingCfg,
+    level: Option<Level>,
+) -> Option<WorkerGuard> {
+    let default_level = level
+        .map(|level| level.to_string())
+        .unwrap_or_else(|| cfg.level.clone());
+    let mut filter = EnvFilter::new(default_level);
+    for (module, level) in &cfg.filters {
+        filter = filter.add_directive(
+            format!("{module}={level}")
+                .parse()
+                .expect("Filters are checked by the config validation"),
+        );
+    }
+
+    let (writer, guard) = match &cfg.file {
+        Some(file) => {
+            let appender = RollingFileAppender::new(
+                rotation(file.rotation),
+                &file.directory,
+                &file.prefix,
+            );
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(std::io::stdout), None),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(cfg.file.is_none());
+    match cfg.format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .init(),
+    }
+
+    guard
+}
+
+fn rotation(rotation: LogRotation) -> Rotation {
+    match rotation {
+        LogRotation::Minutely => Rotation::MINUTELY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    }
+}
@@ -1,48 +1,96 @@
 use std::{
     io,
+    path::PathBuf,
+    process::exit,
     sync::Arc,
 };
 
+use clap::Parser;
 use neogrok::{
-    config::Config,
+    config::overrides::{
+        ConfigSource,
+        Overrides,
+    },
     hisui::server::listen_hisui,
 };
 use tokio::runtime::Builder;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
-fn load_cfg() -> Result<Config, Vec<&'static str>> {
-    let paths = vec![
-        "/etc/neogrok.toml",
-        "/etc/neogrok/server.toml",
-        "/etc/neogrok/neogrok.toml",
-        "./neogrok.toml",
-        "./config/neogrok.toml",
-    ];
-
-    for path in &paths {
-        if let Ok(cfg) = Config::try_load_from(path) {
-            return Ok(cfg);
-        }
-    }
+use crate::cli::{
+    Args,
+    Command,
+};
 
-    Err(paths)
+mod cli;
+mod logging;
+mod train;
+
+const DEFAULT_CONFIG: &str = include_str!("../../../neogrok.toml");
+const DEFAULT_PATHS: [&str; 5] = [
+    "/etc/neogrok.toml",
+    "/etc/neogrok/server.toml",
+    "/etc/neogrok/neogrok.toml",
+    "./neogrok.toml",
+    "./config/neogrok.toml",
+];
+
+fn find_config() -> Option<PathBuf> {
+    DEFAULT_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
 }
 
 fn main() -> io::Result<()> {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
+    let args = Args::parse();
+    if args.print_default_config {
+        print!("{DEFAULT_CONFIG}");
+        return Ok(());
+    }
+    if let Some(Command::TrainDict {
+        output,
+        size,
+        samples,
+    }) = &args.command
+    {
+        return train::train(output, *size, samples);
+    }
+
+    // Used until the logging is configured
+    let bootstrap = FmtSubscriber::builder()
+        .with_max_level(args.log_level.unwrap_or(Level::INFO))
         .finish();
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Failed to set default subscriber");
-    let config: Arc<_> = match load_cfg() {
-        Ok(cfg) => cfg,
-        Err(tried_paths) => {
-            tracing::error!(?tried_paths, "Failed to load config");
-            std::process::exit(1);
-        }
+    let (source, config) = tracing::subscriber::with_default(
+        bootstrap,
+        || {
+            let Some(path) = args.config.or_else(find_config) else {
+                tracing::error!(tried_paths = ?DEFAULT_PATHS, "Config not found");
+                exit(1);
+            };
+            let source = ConfigSource::new(
+                path,
+                Overrides {
+                    listen: args.listen,
+                },
+            );
+
+            match source.load() {
+                Ok(config) => (source, Arc::new(config)),
+                Err(error) => {
+                    tracing::error!(path = %source.path.display(), %error, "Failed to load config");
+                    exit(1);
+                }
+            }
+        },
+    );
+
+    if args.command == Some(Command::CheckConfig) {
+        println!("{}: config is valid", source.path.display());
+        return Ok(());
     }
-    .into();
+
+    let _guard = logging::init(&config.logging, args.log_level);
 
     let rt = match config.runtime.workers {
         1 | 0 => Builder::new_current_thread(),
@@ -53,8 +101,9 @@ fn main() -> io::Result<()> {
         }
     }
     .enable_io()
+    .enable_time()
     .build()
     .expect("Failed to build tokio runtime");
 
-    rt.block_on(listen_hisui(config))
+    rt.block_on(listen_hisui(config, source))
 }
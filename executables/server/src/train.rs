@@ -0,0 +1,52 @@
+use std::{
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use neogrok_protocol::compression::algorithms::zstd::dictionary::{
+    train_dictionary,
+    ZStdDictionary,
+};
+
+/// Trains the dictionary from the `samples` and writes it
+/// to the `output`
+pub fn train(
+    output: &Path,
+    size: usize,
+    samples: &[PathBuf],
+) -> io::Result<()> {
+    let mut payloads = Vec::new();
+    for path in samples {
+        collect_samples(path, &mut payloads)?;
+    }
+
+    let total: usize = payloads.iter().map(Vec::len).sum();
+    println!("training on {} samples, {total} bytes", payloads.len());
+
+    let raw =
+        train_dictionary(&payloads, size).map_err(io::Error::other)?;
+    let id = ZStdDictionary::try_new(raw.clone(), 0)
+        .map_err(io::Error::other)?
+        .id();
+    fs::write(output, &raw)?;
+
+    println!("{}: dictionary {id}, {} bytes", output.display(), raw.len());
+    Ok(())
+}
+
+fn collect_samples(path: &Path, out: &mut Vec<Vec<u8>>) -> io::Result<()> {
+    if !path.is_dir() {
+        out.push(fs::read(path)?);
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(path)? {
+        collect_samples(&entry?.path(), out)?;
+    }
+
+    Ok(())
+}
@@ -26,4 +26,25 @@ pub enum ProtocolError {
 
     #[error("no such client")]
     NoSuchClient = 7,
+
+    #[error("too many access rules specified")]
+    TooManyAccessRules = 8,
+
+    #[error("tunnels quota exceeded")]
+    TunnelQuotaExceeded = 9,
+
+    #[error("clients quota exceeded, client was rejected")]
+    ClientQuotaExceeded = 10,
+
+    #[error("traffic quota exceeded")]
+    TrafficQuotaExceeded = 11,
+
+    #[error("server is shutting down")]
+    ShuttingDown = 12,
+
+    #[error("no free client ids left, client was rejected")]
+    ClientIdsExhausted = 13,
+
+    #[error("requested compression dictionary is not available")]
+    DictionaryMismatch = 14,
 }
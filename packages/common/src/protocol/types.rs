@@ -1,10 +1,14 @@
 use bitflags::bitflags;
 use integral_enum::IntegralEnum;
 
+/// Builtin algorithms, ping response may carry ids of the
+/// custom codecs too
 #[derive(IntegralEnum)]
 pub enum CompressionAlgorithm {
     Deflate = 0,
     ZStd = 1,
+    Lz4 = 2,
+    Brotli = 3,
 }
 
 #[derive(IntegralEnum)]
@@ -38,6 +42,18 @@ bitflags! {
         const CAN_CREATE_HTTP = 1 << 4;
         const CAN_SELECT_HTTP = 1 << 5;
     }
+
+    #[repr(transparent)]
+    pub struct Capabilities: u8 {
+        const CONNECT_METADATA = 1 << 0;
+        const ECHO             = 1 << 1;
+
+        /// Compressed `Forward` frames share history within
+        /// the session, both sides start new streams after the
+        /// capabilities response enabling it and whenever the
+        /// dictionary changes
+        const STREAM_COMPRESSION = 1 << 2;
+    }
 }
 
 impl Rights {
@@ -0,0 +1,125 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use criterion::{
+    criterion_group,
+    criterion_main,
+    BenchmarkId,
+    Criterion,
+};
+use idpool::prelude::*;
+
+const THREADS: [usize; 3] = [1, 4, 8];
+
+/// Every thread requests and immediately returns an id, as
+/// the accept loop and the clients do on a busy tunnel
+fn contended<R>(threads: usize, iters: u64, round: R) -> Duration
+where
+    R: Fn() + Sync,
+{
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                for _ in 0..iters {
+                    round();
+                }
+            });
+        }
+    });
+
+    start.elapsed()
+}
+
+fn bench_pools(c: &mut Criterion) {
+    let mut group = c.benchmark_group("request_return");
+    for threads in THREADS {
+        group.bench_with_input(
+            BenchmarkId::new("mutex_flat", threads),
+            &threads,
+            |b, &threads| {
+                let pool = Mutex::new(FlatIdPool::<u16>::zero());
+                b.iter_custom(|iters| {
+                    contended(threads, iters, || {
+                        let id = pool.lock().unwrap().request_id();
+                        pool.lock().unwrap().return_id(id);
+                    })
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("mutex_priority", threads),
+            &threads,
+            |b, &threads| {
+                let pool =
+                    Mutex::new(PriorityIdPool::<u16, LowToHigh>::zero());
+                b.iter_custom(|iters| {
+                    contended(threads, iters, || {
+                        let id = pool.lock().unwrap().request_id();
+                        pool.lock().unwrap().return_id(id);
+                    })
+                });
+            },
+        );
+
+        // What the server used before the concurrent pool
+        group.bench_with_input(
+            BenchmarkId::new("tokio_mutex_generational", threads),
+            &threads,
+            |b, &threads| {
+                let pool = tokio::sync::Mutex::new(
+                    GenerationalIdPool::new(0..=u16::MAX, Duration::ZERO),
+                );
+                b.iter_custom(|iters| {
+                    contended(threads, iters, || {
+                        let id =
+                            pool.blocking_lock().request_id().unwrap();
+                        pool.blocking_lock().return_id(id);
+                    })
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("concurrent", threads),
+            &threads,
+            |b, &threads| {
+                let pool = ConcurrentIdPool::new(0..=u16::MAX);
+                b.iter_custom(|iters| {
+                    contended(threads, iters, || {
+                        let id = pool.request_id().unwrap();
+                        pool.return_id(id);
+                    })
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("concurrent_generational", threads),
+            &threads,
+            |b, &threads| {
+                let pool = ConcurrentGenerationalIdPool::new(
+                    0..=u16::MAX,
+                    Duration::ZERO,
+                );
+                b.iter_custom(|iters| {
+                    contended(threads, iters, || {
+                        let id = pool.request_id().unwrap();
+                        pool.return_id(id);
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pools);
+criterion_main!(benches);
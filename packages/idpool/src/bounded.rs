@@ -0,0 +1,141 @@
+use std::{
+    error::Error,
+    fmt::{
+        self,
+        Display,
+    },
+    ops::RangeInclusive,
+};
+
+use num::{
+    Integer,
+    ToPrimitive,
+};
+
+/// All ids of the pool's range are in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolExhausted;
+
+impl Display for PoolExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("all ids of the pool are in use")
+    }
+}
+
+impl Error for PoolExhausted {}
+
+/// Yields ids of the `[min, max]` range which were never
+/// handed out
+#[derive(Debug, Clone)]
+pub(crate) struct FreshIds<T> {
+    next: Option<T>,
+    max: T,
+}
+
+impl<T> FreshIds<T>
+where
+    T: Integer + Copy,
+{
+    pub(crate) fn next(&mut self) -> Option<T> {
+        let id = self.next?;
+        self.next = if id == self.max {
+            None
+        } else {
+            Some(id + T::one())
+        };
+
+        Some(id)
+    }
+
+    pub(crate) fn new(range: RangeInclusive<T>) -> Self {
+        let (min, max) = range.into_inner();
+        Self {
+            next: (min <= max).then_some(min),
+            max,
+        }
+    }
+}
+
+/// Id pool limited to the `[min, max]` range, reports
+/// exhaustion instead of overflowing
+#[derive(Debug)]
+pub struct BoundedIdPool<T> {
+    free: Vec<T>,
+    fresh: FreshIds<T>,
+
+    min: T,
+    /// Bit per id of the range, set while it's in use
+    taken: Vec<u64>,
+    in_use: usize,
+}
+
+impl<T> BoundedIdPool<T>
+where
+    T: Integer + Copy + ToPrimitive,
+{
+    pub fn request_id(&mut self) -> Result<T, PoolExhausted> {
+        let id = self
+            .free
+            .pop()
+            .or_else(|| self.fresh.next())
+            .ok_or(PoolExhausted)?;
+        self.set_taken(id, true);
+        self.in_use += 1;
+
+        Ok(id)
+    }
+
+    /// Ids which are not in use are ignored, returns
+    /// whether the id was actually released
+    pub fn return_id(&mut self, id: T) -> bool {
+        if !self.set_taken(id, false) {
+            return false;
+        }
+
+        self.free.push(id);
+        self.in_use -= 1;
+        true
+    }
+
+    /// Number of currently requested ids
+    pub const fn in_use(&self) -> usize {
+        self.in_use
+    }
+
+    /// Returns previous state of the id's bit, ids out of
+    /// the range are never taken
+    fn set_taken(&mut self, id: T, taken: bool) -> bool {
+        let Some(index) = (id >= self.min)
+            .then(|| (id - self.min).to_usize())
+            .flatten()
+        else {
+            return false;
+        };
+        let (word, bit) = (index / 64, 1_u64 << (index % 64));
+        if word >= self.taken.len() {
+            if !taken {
+                return false;
+            }
+            self.taken.resize(word + 1, 0);
+        }
+
+        let was_taken = self.taken[word] & bit != 0;
+        if taken {
+            self.taken[word] |= bit;
+        } else {
+            self.taken[word] &= !bit;
+        }
+
+        was_taken
+    }
+
+    pub fn new(range: RangeInclusive<T>) -> Self {
+        Self {
+            free: Vec::new(),
+            min: *range.start(),
+            fresh: FreshIds::new(range),
+            taken: Vec::new(),
+            in_use: 0,
+        }
+    }
+}
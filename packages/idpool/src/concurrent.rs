@@ -0,0 +1,385 @@
+use std::{
+    ops::RangeInclusive,
+    sync::{
+        atomic::{
+            AtomicU32,
+            AtomicU64,
+            AtomicUsize,
+            Ordering,
+        },
+        OnceLock,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use num::PrimInt;
+
+use crate::{
+    bounded::PoolExhausted,
+    generational::GenerationalId,
+};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Lock-free set of taken indices. Search for the free
+/// index starts from the rotating cursor, so freshly
+/// returned indices are not reused right away and
+/// concurrent requesters rarely race for the same word.
+#[derive(Debug)]
+struct Bitmap {
+    words: Box<[AtomicU64]>,
+    len: usize,
+
+    cursor: AtomicUsize,
+}
+
+impl Bitmap {
+    /// Takes the first free index for which `usable`
+    /// returns `true`
+    fn acquire(
+        &self,
+        mut usable: impl FnMut(usize) -> bool,
+    ) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let start = self.cursor.load(Ordering::Relaxed);
+        let (start_word, start_bit) =
+            (start / WORD_BITS, start % WORD_BITS);
+
+        // Start word is visited twice: first from the cursor,
+        // then as a whole after wrapping around
+        for step in 0..=self.words.len() {
+            let word_index = (start_word + step) % self.words.len();
+            let mut mask = self.valid_mask(word_index);
+            if step == 0 {
+                mask &= u64::MAX << start_bit;
+            }
+
+            let word = &self.words[word_index];
+            let mut current = word.load(Ordering::Acquire);
+            loop {
+                let mut candidates = !current & mask;
+                let bit = loop {
+                    if candidates == 0 {
+                        break None;
+                    }
+
+                    let bit = candidates.trailing_zeros() as usize;
+                    if usable(word_index * WORD_BITS + bit) {
+                        break Some(bit);
+                    }
+                    candidates &= candidates - 1;
+                };
+                let Some(bit) = bit else {
+                    break;
+                };
+
+                match word.compare_exchange_weak(
+                    current,
+                    current | (1 << bit),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => {
+                        let index = word_index * WORD_BITS + bit;
+                        self.cursor.store(
+                            (index + 1) % self.len,
+                            Ordering::Relaxed,
+                        );
+
+                        return Some(index);
+                    }
+
+                    Err(actual) => current = actual,
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns `false` if `index` was not taken
+    fn release(&self, index: usize) -> bool {
+        let bit = 1 << (index % WORD_BITS);
+        let prev = self.words[index / WORD_BITS]
+            .fetch_and(!bit, Ordering::Release);
+        prev & bit != 0
+    }
+
+    fn is_taken(&self, index: usize) -> bool {
+        let bit = 1 << (index % WORD_BITS);
+        self.words[index / WORD_BITS].load(Ordering::Acquire) & bit != 0
+    }
+
+    /// Counted on demand, so requesters don't contend on
+    /// the shared counter
+    fn in_use(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.load(Ordering::Relaxed).count_ones() as usize)
+            .sum()
+    }
+
+    /// Bits of the word which map to the indices
+    fn valid_mask(&self, word_index: usize) -> u64 {
+        let tail = self.len % WORD_BITS;
+        if tail != 0 && word_index == self.words.len() - 1 {
+            (1 << tail) - 1
+        } else {
+            u64::MAX
+        }
+    }
+
+    fn new(len: usize) -> Self {
+        Self {
+            words: (0..len.div_ceil(WORD_BITS))
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            len,
+            cursor: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Maps ids of the `[min, max]` range to the bitmap indices
+#[derive(Debug, Clone, Copy)]
+struct IdRange<T> {
+    min: T,
+    len: usize,
+}
+
+impl<T: PrimInt> IdRange<T> {
+    fn index(&self, id: T) -> Option<usize> {
+        if id < self.min {
+            return None;
+        }
+
+        (id - self.min)
+            .to_usize()
+            .filter(|&index| index < self.len)
+    }
+
+    fn id(&self, index: usize) -> T {
+        self.min
+            + T::from(index).expect("Index is always within the range")
+    }
+
+    fn new(range: RangeInclusive<T>) -> Self {
+        let (min, max) = range.into_inner();
+        let len = if min > max {
+            0
+        } else {
+            (max - min)
+                .to_usize()
+                .and_then(|len| len.checked_add(1))
+                .expect("Id range must fit into the memory")
+        };
+
+        Self { min, len }
+    }
+}
+
+/// Lock-free id pool limited to the `[min, max]` range,
+/// can be shared between threads without a mutex. Ids are
+/// handed out round-robin.
+#[derive(Debug)]
+pub struct ConcurrentIdPool<T> {
+    bitmap: Bitmap,
+    range: IdRange<T>,
+}
+
+impl<T: PrimInt> ConcurrentIdPool<T> {
+    pub fn request_id(&self) -> Result<T, PoolExhausted> {
+        self.bitmap
+            .acquire(|_| true)
+            .map(|index| self.range.id(index))
+            .ok_or(PoolExhausted)
+    }
+
+    /// Ids which were not requested are ignored
+    pub fn return_id(&self, id: T) {
+        if let Some(index) = self.range.index(id) {
+            self.bitmap.release(index);
+        }
+    }
+
+    /// Number of currently requested ids
+    pub fn in_use(&self) -> usize {
+        self.bitmap.in_use()
+    }
+
+    pub fn new(range: RangeInclusive<T>) -> Self {
+        let range = IdRange::new(range);
+        Self {
+            bitmap: Bitmap::new(range.len),
+            range,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    generation: AtomicU32,
+
+    /// Nanoseconds since the pool's epoch
+    released_at: AtomicU64,
+}
+
+/// Lock-free counterpart of the
+/// [`GenerationalIdPool`](crate::generational::GenerationalIdPool).
+/// Returned ids are kept in quarantine, but reuse order is
+/// round-robin instead of the return order.
+///
+/// Slots are allocated lazily in chunks of 64, so large
+/// ranges don't cost much until used.
+#[derive(Debug)]
+pub struct ConcurrentGenerationalIdPool<T> {
+    bitmap: Bitmap,
+    chunks: Box<[OnceLock<Box<[Slot]>>]>,
+    range: IdRange<T>,
+
+    epoch: Instant,
+    quarantine: Duration,
+}
+
+impl<T: PrimInt> ConcurrentGenerationalIdPool<T> {
+    pub fn request_id(&self) -> Result<GenerationalId<T>, PoolExhausted> {
+        self.request_id_at(Instant::now())
+    }
+
+    pub fn request_id_at(
+        &self,
+        now: Instant,
+    ) -> Result<GenerationalId<T>, PoolExhausted> {
+        let elapsed = self.since_epoch(now);
+        let quarantine = self.quarantine.as_nanos() as u64;
+        let index = self
+            .bitmap
+            .acquire(|index| {
+                // Never released slots have zero generation
+                self.allocated_slot(index).is_none_or(|slot| {
+                    slot.generation.load(Ordering::Relaxed) == 0
+                        || elapsed.saturating_sub(
+                            slot.released_at.load(Ordering::Relaxed),
+                        ) >= quarantine
+                })
+            })
+            .ok_or(PoolExhausted)?;
+
+        Ok(GenerationalId {
+            id: self.range.id(index),
+            generation: self
+                .slot(index)
+                .generation
+                .load(Ordering::Acquire),
+        })
+    }
+
+    /// Returns `false` if `id` is stale, such id is not
+    /// returned twice
+    pub fn return_id(&self, id: GenerationalId<T>) -> bool {
+        self.return_id_at(id, Instant::now())
+    }
+
+    pub fn return_id_at(
+        &self,
+        id: GenerationalId<T>,
+        now: Instant,
+    ) -> bool {
+        let Some(index) = self.range.index(id.id) else {
+            return false;
+        };
+        if !self.bitmap.is_taken(index) {
+            return false;
+        }
+        let Some(slot) = self.allocated_slot(index) else {
+            return false;
+        };
+
+        // Only one of the concurrent returners bumps the
+        // generation
+        if slot
+            .generation
+            .compare_exchange(
+                id.generation,
+                id.generation.wrapping_add(1),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return false;
+        }
+        slot.released_at
+            .store(self.since_epoch(now), Ordering::Relaxed);
+        self.bitmap.release(index)
+    }
+
+    /// Whether `id` is still held by its owner
+    pub fn is_current(&self, id: GenerationalId<T>) -> bool {
+        self.range.index(id.id).is_some_and(|index| {
+            self.bitmap.is_taken(index)
+                && self.allocated_slot(index).is_some_and(|slot| {
+                    slot.generation.load(Ordering::Acquire)
+                        == id.generation
+                })
+        })
+    }
+
+    /// Whether `id` was handed out before, but is not in
+    /// use right now. Anything addressed to such id is
+    /// stale.
+    pub fn is_released(&self, id: T) -> bool {
+        self.range.index(id).is_some_and(|index| {
+            !self.bitmap.is_taken(index)
+                && self.allocated_slot(index).is_some_and(|slot| {
+                    slot.generation.load(Ordering::Acquire) != 0
+                })
+        })
+    }
+
+    /// Number of currently requested ids, quarantined ids
+    /// are not counted
+    pub fn in_use(&self) -> usize {
+        self.bitmap.in_use()
+    }
+
+    fn allocated_slot(&self, index: usize) -> Option<&Slot> {
+        self.chunks[index / WORD_BITS]
+            .get()
+            .map(|chunk| &chunk[index % WORD_BITS])
+    }
+
+    fn slot(&self, index: usize) -> &Slot {
+        let chunk = self.chunks[index / WORD_BITS].get_or_init(|| {
+            (0..WORD_BITS).map(|_| Slot::default()).collect()
+        });
+        &chunk[index % WORD_BITS]
+    }
+
+    fn since_epoch(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.epoch)
+            .as_nanos() as u64
+    }
+
+    pub fn new(range: RangeInclusive<T>, quarantine: Duration) -> Self {
+        let range = IdRange::new(range);
+        let bitmap = Bitmap::new(range.len);
+        let chunks = (0..bitmap.words.len())
+            .map(|_| OnceLock::new())
+            .collect();
+
+        Self {
+            bitmap,
+            chunks,
+            range,
+            epoch: Instant::now(),
+            quarantine,
+        }
+    }
+}
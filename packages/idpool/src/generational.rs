@@ -0,0 +1,149 @@
+use std::{
+    collections::{
+        HashMap,
+        VecDeque,
+    },
+    hash::Hash,
+    ops::RangeInclusive,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use num::Integer;
+
+use crate::bounded::{
+    FreshIds,
+    PoolExhausted,
+};
+
+/// Id paired with the generation of its slot. Generation
+/// is bumped every time the id is returned to the pool, so
+/// the old holders can be told apart from the new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GenerationalId<T> {
+    pub id: T,
+    pub generation: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    generation: u32,
+    in_use: bool,
+}
+
+/// Id pool which keeps returned ids in quarantine for the
+/// specified duration before handing them out again. Ids
+/// are reused in the order they were returned.
+///
+/// Pool is limited to the `[min, max]` range, quarantined
+/// ids are not handed out even if the range is exhausted.
+#[derive(Debug)]
+pub struct GenerationalIdPool<T> {
+    released: VecDeque<(T, Instant)>,
+    slots: HashMap<T, Slot>,
+
+    fresh: FreshIds<T>,
+    quarantine: Duration,
+    in_use: usize,
+}
+
+impl<T> GenerationalIdPool<T>
+where
+    T: Integer + Copy + Hash,
+{
+    pub fn request_id(
+        &mut self,
+    ) -> Result<GenerationalId<T>, PoolExhausted> {
+        self.request_id_at(Instant::now())
+    }
+
+    pub fn request_id_at(
+        &mut self,
+        now: Instant,
+    ) -> Result<GenerationalId<T>, PoolExhausted> {
+        let id = match self.released.front() {
+            Some(&(id, released_at))
+                if now.saturating_duration_since(released_at)
+                    >= self.quarantine =>
+            {
+                self.released.pop_front();
+                id
+            }
+
+            _ => self.fresh.next().ok_or(PoolExhausted)?,
+        };
+
+        let slot = self.slots.entry(id).or_insert(Slot {
+            generation: 0,
+            in_use: false,
+        });
+        slot.in_use = true;
+        self.in_use += 1;
+
+        Ok(GenerationalId {
+            id,
+            generation: slot.generation,
+        })
+    }
+
+    /// Returns `false` if `id` is stale, such id is not
+    /// returned twice
+    pub fn return_id(&mut self, id: GenerationalId<T>) -> bool {
+        self.return_id_at(id, Instant::now())
+    }
+
+    pub fn return_id_at(
+        &mut self,
+        id: GenerationalId<T>,
+        now: Instant,
+    ) -> bool {
+        if !self.is_current(id) {
+            return false;
+        }
+
+        let slot = self
+            .slots
+            .get_mut(&id.id)
+            .expect("Current id always has a slot");
+        slot.in_use = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.released.push_back((id.id, now));
+        self.in_use -= 1;
+
+        true
+    }
+
+    /// Whether `id` is still held by its owner
+    pub fn is_current(&self, id: GenerationalId<T>) -> bool {
+        self.slots.get(&id.id).is_some_and(|slot| {
+            slot.in_use && slot.generation == id.generation
+        })
+    }
+
+    /// Whether `id` was handed out before, but is not in
+    /// use right now. Anything addressed to such id is
+    /// stale.
+    pub fn is_released(&self, id: T) -> bool {
+        self.slots
+            .get(&id)
+            .is_some_and(|slot| !slot.in_use)
+    }
+
+    /// Number of currently requested ids, quarantined ids
+    /// are not counted
+    pub const fn in_use(&self) -> usize {
+        self.in_use
+    }
+
+    pub fn new(range: RangeInclusive<T>, quarantine: Duration) -> Self {
+        Self {
+            released: VecDeque::new(),
+            slots: HashMap::new(),
+            fresh: FreshIds::new(range),
+            quarantine,
+            in_use: 0,
+        }
+    }
+}
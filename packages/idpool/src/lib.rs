@@ -1,4 +1,7 @@
+pub mod bounded;
+pub mod concurrent;
 pub mod flat;
+pub mod generational;
 pub mod priority;
 
 pub mod prelude;
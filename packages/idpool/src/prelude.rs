@@ -1,4 +1,7 @@
 pub use crate::{
+    bounded::*,
+    concurrent::*,
     flat::*,
+    generational::*,
     priority::*,
 };
@@ -1,5 +1,18 @@
+use std::{
+    collections::HashSet,
+    sync::Mutex,
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
 use crate::{
+    bounded::*,
+    concurrent::*,
     flat::*,
+    generational::*,
     priority::*,
 };
 
@@ -31,3 +44,184 @@ fn test_flat_idpool() {
 
     assert_eq!(pool.request_id(), 0);
 }
+
+#[test]
+fn test_generational_idpool_quarantine() {
+    let quarantine = Duration::from_secs(10);
+    let start = Instant::now();
+    let mut pool = GenerationalIdPool::new(0..=u16::MAX, quarantine);
+
+    let first = pool.request_id_at(start).unwrap();
+    assert_eq!(
+        first,
+        GenerationalId {
+            id: 0,
+            generation: 0
+        }
+    );
+    assert!(pool.return_id_at(first, start));
+    assert!(pool.is_released(0));
+
+    // Returned id is quarantined
+    let second = pool
+        .request_id_at(start + quarantine / 2)
+        .unwrap();
+    assert_eq!(second.id, 1);
+
+    let reused = pool.request_id_at(start + quarantine).unwrap();
+    assert_eq!(
+        reused,
+        GenerationalId {
+            id: 0,
+            generation: 1
+        }
+    );
+    assert!(!pool.is_released(0));
+}
+
+#[test]
+fn test_generational_idpool_stale() {
+    let start = Instant::now();
+    let mut pool = GenerationalIdPool::new(0..=u16::MAX, Duration::ZERO);
+
+    let old = pool.request_id_at(start).unwrap();
+    assert!(pool.return_id_at(old, start));
+    let new = pool.request_id_at(start).unwrap();
+
+    assert_eq!(old.id, new.id);
+    assert!(!pool.is_current(old));
+    assert!(pool.is_current(new));
+
+    // Stale id is not returned twice
+    assert!(!pool.return_id_at(old, start));
+    assert!(pool.is_current(new));
+}
+
+#[test]
+fn test_bounded_idpool_exhaustion() {
+    let mut pool = BoundedIdPool::<u8>::new(250..=u8::MAX);
+    for expected in 250..=u8::MAX {
+        assert_eq!(pool.request_id(), Ok(expected));
+    }
+    assert_eq!(pool.in_use(), 6);
+    assert_eq!(pool.request_id(), Err(PoolExhausted));
+
+    pool.return_id(252);
+    assert_eq!(pool.in_use(), 5);
+    assert_eq!(pool.request_id(), Ok(252));
+    assert_eq!(pool.request_id(), Err(PoolExhausted));
+}
+
+#[test]
+fn test_bounded_idpool_invalid_return() {
+    let mut pool = BoundedIdPool::<u8>::new(10..=20);
+    let id = pool.request_id().unwrap();
+
+    // Out of the range and never handed out
+    assert!(!pool.return_id(9));
+    assert!(!pool.return_id(21));
+    assert!(!pool.return_id(15));
+    assert_eq!(pool.in_use(), 1);
+
+    // Double return doesn't hand the id out twice
+    assert!(pool.return_id(id));
+    assert!(!pool.return_id(id));
+    assert_eq!(pool.in_use(), 0);
+    assert_eq!(pool.request_id(), Ok(id));
+    assert_ne!(pool.request_id(), Ok(id));
+}
+
+#[test]
+fn test_generational_idpool_exhaustion() {
+    let start = Instant::now();
+    let quarantine = Duration::from_secs(1);
+    let mut pool = GenerationalIdPool::new(1..=2_u16, quarantine);
+
+    let first = pool.request_id_at(start).unwrap();
+    pool.request_id_at(start).unwrap();
+    assert_eq!(pool.request_id_at(start), Err(PoolExhausted));
+
+    // Quarantined id is not handed out
+    pool.return_id_at(first, start);
+    assert_eq!(pool.in_use(), 1);
+    assert_eq!(pool.request_id_at(start), Err(PoolExhausted));
+    assert_eq!(
+        pool.request_id_at(start + quarantine)
+            .map(|id| id.id),
+        Ok(1)
+    );
+}
+
+#[test]
+fn test_concurrent_idpool() {
+    let pool = ConcurrentIdPool::<u8>::new(250..=u8::MAX);
+    for expected in 250..=u8::MAX {
+        assert_eq!(pool.request_id(), Ok(expected));
+    }
+    assert_eq!(pool.in_use(), 6);
+    assert_eq!(pool.request_id(), Err(PoolExhausted));
+
+    pool.return_id(252);
+    assert_eq!(pool.in_use(), 5);
+    assert_eq!(pool.request_id(), Ok(252));
+
+    // Empty range
+    #[allow(clippy::reversed_empty_ranges)]
+    let empty = ConcurrentIdPool::<u8>::new(1..=0);
+    assert_eq!(empty.request_id(), Err(PoolExhausted));
+
+    // Search continues after the last handed out id
+    pool.return_id(250);
+    pool.return_id(254);
+    assert_eq!(pool.request_id(), Ok(254));
+    assert_eq!(pool.request_id(), Ok(250));
+}
+
+#[test]
+fn test_concurrent_idpool_threads() {
+    const THREADS: usize = 8;
+    const PER_THREAD: usize = 1000;
+
+    let pool = ConcurrentIdPool::<u16>::new(0..=u16::MAX);
+    let taken = Mutex::new(HashSet::new());
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..PER_THREAD {
+                    let id = pool.request_id().unwrap();
+                    assert!(taken.lock().unwrap().insert(id));
+                }
+            });
+        }
+    });
+
+    assert_eq!(pool.in_use(), THREADS * PER_THREAD);
+    assert_eq!(taken.into_inner().unwrap().len(), THREADS * PER_THREAD);
+}
+
+#[test]
+fn test_concurrent_generational_idpool() {
+    let quarantine = Duration::from_secs(1);
+    let pool = ConcurrentGenerationalIdPool::new(1..=2_u16, quarantine);
+    let start = Instant::now();
+
+    let first = pool.request_id_at(start).unwrap();
+    let second = pool.request_id_at(start).unwrap();
+    assert_eq!((first.id, second.id), (1, 2));
+    assert!(!pool.is_released(1));
+    assert_eq!(pool.request_id_at(start), Err(PoolExhausted));
+
+    assert!(pool.return_id_at(first, start));
+    assert!(!pool.return_id_at(first, start));
+    assert!(pool.is_released(1));
+    assert!(!pool.is_current(first));
+    assert_eq!(pool.in_use(), 1);
+
+    // Quarantined id is not handed out
+    assert_eq!(pool.request_id_at(start), Err(PoolExhausted));
+    let reused = pool.request_id_at(start + quarantine).unwrap();
+    assert_eq!(reused.id, 1);
+    assert_ne!(reused.generation, first.generation);
+    assert!(pool.is_current(reused));
+    assert!(!pool.return_id_at(first, start));
+}
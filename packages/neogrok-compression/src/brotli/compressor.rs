@@ -0,0 +1,59 @@
+use brotli::enc::{
+    BrotliCompress,
+    BrotliEncoderParams,
+};
+
+use crate::{
+    error::{
+        CompressorInitError,
+        CompressorInitResult,
+    },
+    traits::Compressor,
+};
+
+const MAX_QUALITY: u8 = 11;
+
+/// Window of 64KiB covers the largest forwarded buffer
+const WINDOW_LOG: i32 = 16;
+
+/// Brotli compressor, best suited for the text-heavy
+/// traffic
+pub struct BrotliCompressor {
+    params: BrotliEncoderParams,
+}
+
+impl Compressor for BrotliCompressor {
+    fn compress(
+        &mut self,
+        mut buffer: &[u8],
+        max_allocate_size: usize,
+    ) -> Option<Vec<u8>> {
+        let mut params = self.params.clone();
+        params.size_hint = buffer.len();
+
+        let mut out = Vec::with_capacity(max_allocate_size);
+        BrotliCompress(&mut buffer, &mut out, &params).ok()?;
+
+        (out.len() <= max_allocate_size).then_some(out)
+    }
+}
+
+impl BrotliCompressor {
+    pub fn try_new(level: u8) -> CompressorInitResult<Self> {
+        if level > MAX_QUALITY {
+            return Err(CompressorInitError::InvalidCompressionLevel);
+        }
+
+        Ok(Self {
+            params: BrotliEncoderParams {
+                quality: level as i32,
+                lgwin: WINDOW_LOG,
+                ..Default::default()
+            },
+        })
+    }
+
+    pub fn new(level: u8) -> Self {
+        Self::try_new(level).expect("Failed to create compressor")
+    }
+}
@@ -0,0 +1,59 @@
+use std::io::Read;
+
+use brotli::Decompressor as BrotliReader;
+
+use crate::{
+    error::{
+        DecompressError,
+        DecompressResult,
+    },
+    traits::{
+        Decompressor,
+        MAX_UNCONSTRAINED_SIZE,
+    },
+};
+
+/// Size of the decoder's input buffer
+const INPUT_BUFFER_SIZE: usize = 4096;
+
+#[derive(Debug, Default)]
+pub struct BrotliDecompressor;
+
+impl Decompressor for BrotliDecompressor {
+    fn decompress(
+        &mut self,
+        buffer: &[u8],
+        max_decompressed_size: usize,
+    ) -> DecompressResult<Vec<u8>> {
+        // One byte past the limit tells whether the output
+        // is exactly `max_decompressed_size` long or longer
+        let limit = max_decompressed_size.saturating_add(1);
+        let mut out = Vec::with_capacity(limit.min(buffer.len() << 2));
+
+        BrotliReader::new(buffer, INPUT_BUFFER_SIZE)
+            .take(limit as u64)
+            .read_to_end(&mut out)
+            .map_err(|_| DecompressError::InvalidCompressedData)?;
+
+        if out.len() > max_decompressed_size {
+            Err(DecompressError::InsufficientSpace)
+        } else {
+            Ok(out)
+        }
+    }
+
+    /// Output is grown while decoding, so the largest limit
+    /// is used right away
+    fn decompress_unconstrained(
+        &mut self,
+        src: &[u8],
+    ) -> DecompressResult<Vec<u8>> {
+        self.decompress(src, MAX_UNCONSTRAINED_SIZE)
+    }
+}
+
+impl BrotliDecompressor {
+    pub fn new() -> Self {
+        Self
+    }
+}
@@ -0,0 +1,35 @@
+use super::{
+    compressor::*,
+    decompressor::*,
+};
+use crate::{
+    error::DecompressError,
+    traits::*,
+};
+
+#[test]
+fn test_compression() {
+    let buffer =
+        b"<html><body><p>Hello world, guys</p></body></html>".repeat(4);
+
+    let mut compressor = BrotliCompressor::new(11);
+    let mut decompressor = BrotliDecompressor::new();
+
+    let compressed_buf = compressor
+        .compress(&buffer, buffer.len())
+        .unwrap();
+    let decompressed_buf = decompressor
+        .decompress(&compressed_buf, buffer.len())
+        .unwrap();
+
+    assert_eq!(decompressed_buf, buffer);
+    assert_eq!(
+        decompressor.decompress(&compressed_buf, buffer.len() - 1),
+        Err(DecompressError::InsufficientSpace)
+    );
+    assert_eq!(
+        decompressor.decompress(b"definitely not brotli", 1024),
+        Err(DecompressError::InvalidCompressedData)
+    );
+    assert_eq!(compressor.compress(&buffer, 4), None);
+}
@@ -11,17 +11,20 @@ use libdeflate_sys::{
     libdeflate_free_compressor,
 };
 
-use crate::error::{
-    CompressorInitError,
-    CompressorInitResult,
+use crate::{
+    error::{
+        CompressorInitError,
+        CompressorInitResult,
+    },
+    traits::Compressor,
 };
 
 pub struct DeflateCompressor {
     ptr: NonNull<libdeflate_compressor>,
 }
 
-impl DeflateCompressor {
-    pub fn compress(
+impl Compressor for DeflateCompressor {
+    fn compress(
         &mut self,
         buffer: &[u8],
         max_available: usize,
@@ -55,7 +58,9 @@ impl DeflateCompressor {
             }
         }
     }
+}
 
+impl DeflateCompressor {
     pub fn try_new(level: u8) -> CompressorInitResult<Self> {
         if level > 12 {
             return Err(CompressorInitError::InvalidCompressionLevel);
@@ -81,3 +86,6 @@ impl Drop for DeflateCompressor {
         }
     }
 }
+
+// libdeflate objects have no thread affinity
+unsafe impl Send for DeflateCompressor {}
@@ -12,19 +12,22 @@ use libdeflate_sys::{
     libdeflate_result_LIBDEFLATE_SUCCESS,
 };
 
-use crate::error::{
-    DecompressError,
-    DecompressResult,
-    DecompressorError,
-    DecompressorInitResult,
+use crate::{
+    error::{
+        DecompressError,
+        DecompressResult,
+        DecompressorError,
+        DecompressorInitResult,
+    },
+    traits::Decompressor,
 };
 
 pub struct DeflateDecompressor {
     ptr: NonNull<libdeflate_decompressor>,
 }
 
-impl DeflateDecompressor {
-    pub fn decompress(
+impl Decompressor for DeflateDecompressor {
+    fn decompress(
         &mut self,
         buffer: &[u8],
         max_decompressed_size: usize,
@@ -67,7 +70,9 @@ impl DeflateDecompressor {
             }
         }
     }
+}
 
+impl DeflateDecompressor {
     pub fn try_new() -> DecompressorInitResult<Self> {
         NonNull::new(unsafe { libdeflate_alloc_decompressor() })
             .map(|ptr| Self { ptr })
@@ -90,3 +95,6 @@ impl Drop for DeflateDecompressor {
         unsafe { libdeflate_free_decompressor(self.ptr.as_ptr()) }
     }
 }
+
+// Same as the compressor, nothing ties it to the thread
+unsafe impl Send for DeflateDecompressor {}
@@ -2,6 +2,7 @@ use super::{
     compressor::*,
     decompressor::*,
 };
+use crate::traits::*;
 
 #[test]
 fn test_compression() {
@@ -2,17 +2,23 @@ use neogrok_declmacro::{
     compress::define_copyable_compress_errors,
     define_results,
 };
+use thiserror::Error;
+
+use crate::registry::AlgorithmId;
 
 define_results! {
     CompressorInitResult<T>   = <CompressorInitError>,
     DecompressorInitResult<T> = <DecompressorError>,
     DecompressResult<T>       = <DecompressError>,
+    DictionaryResult<T>       = <DictionaryError>,
+    CodecResult<T>            = <CodecError>,
 }
 
 define_copyable_compress_errors! {
     enum DecompressError {
         InvalidCompressedData = "Got invalid compressed data",
         InsufficientSpace = "Insufficient destination buffer size for the compressed data",
+        DictionaryMismatch = "Data was compressed with a different dictionary",
     }
 
     enum DecompressorError {
@@ -23,4 +29,22 @@ define_copyable_compress_errors! {
         FailedToAllocate = "Failed to allocate compressor",
         InvalidCompressionLevel = "Invalid compression level specified"
     }
+
+    enum DictionaryError {
+        InvalidDictionary = "Not a zstd dictionary or dictionary has no id",
+        FailedToAllocate = "Failed to allocate dictionary",
+        TrainingFailed = "Failed to train dictionary, more samples are needed",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum CodecError {
+    #[error("No codec is registered for the algorithm {0}")]
+    UnknownAlgorithm(AlgorithmId),
+
+    #[error(transparent)]
+    Compressor(#[from] CompressorInitError),
+
+    #[error(transparent)]
+    Decompressor(#[from] DecompressorError),
 }
@@ -1,6 +1,13 @@
 pub mod error;
 
+pub mod brotli;
 pub mod deflate;
+pub mod lz4;
 pub mod zstd;
 
 pub mod polymorphic;
+pub mod registry;
+pub mod traits;
+
+#[cfg(test)]
+mod tests;
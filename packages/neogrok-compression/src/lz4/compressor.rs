@@ -0,0 +1,35 @@
+use lz4_flex::block::{
+    compress_into,
+    get_maximum_output_size,
+};
+
+use crate::traits::Compressor;
+
+/// LZ4 block compressor, trades ratio for the speed. LZ4
+/// has no compression levels.
+#[derive(Debug, Default)]
+pub struct Lz4Compressor;
+
+impl Compressor for Lz4Compressor {
+    fn compress(
+        &mut self,
+        buffer: &[u8],
+        max_allocate_size: usize,
+    ) -> Option<Vec<u8>> {
+        // Encoder requires room for the worst case up front
+        let mut out = vec![0; get_maximum_output_size(buffer.len())];
+        let size = compress_into(buffer, &mut out).ok()?;
+        if size > max_allocate_size {
+            return None;
+        }
+        out.truncate(size);
+
+        Some(out)
+    }
+}
+
+impl Lz4Compressor {
+    pub fn new() -> Self {
+        Self
+    }
+}
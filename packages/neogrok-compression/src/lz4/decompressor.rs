@@ -0,0 +1,44 @@
+use lz4_flex::block::{
+    self,
+    decompress_into,
+};
+
+use crate::{
+    error::{
+        DecompressError,
+        DecompressResult,
+    },
+    traits::Decompressor,
+};
+
+/// Blocks carry no decompressed size, so the output is
+/// limited by the destination buffer alone
+#[derive(Debug, Default)]
+pub struct Lz4Decompressor;
+
+impl Decompressor for Lz4Decompressor {
+    fn decompress(
+        &mut self,
+        buffer: &[u8],
+        max_decompressed_size: usize,
+    ) -> DecompressResult<Vec<u8>> {
+        let mut out = vec![0; max_decompressed_size];
+        match decompress_into(buffer, &mut out) {
+            Ok(size) => {
+                out.truncate(size);
+                Ok(out)
+            }
+
+            Err(block::DecompressError::OutputTooSmall { .. }) => {
+                Err(DecompressError::InsufficientSpace)
+            }
+            Err(_) => Err(DecompressError::InvalidCompressedData),
+        }
+    }
+}
+
+impl Lz4Decompressor {
+    pub fn new() -> Self {
+        Self
+    }
+}
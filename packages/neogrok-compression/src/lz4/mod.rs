@@ -0,0 +1,5 @@
+pub mod compressor;
+pub mod decompressor;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,30 @@
+use super::{
+    compressor::*,
+    decompressor::*,
+};
+use crate::{
+    error::DecompressError,
+    traits::*,
+};
+
+#[test]
+fn test_compression() {
+    let buffer = b"Hello world, guys. Hello world, guys!".repeat(4);
+
+    let mut compressor = Lz4Compressor::new();
+    let mut decompressor = Lz4Decompressor::new();
+
+    let compressed_buf = compressor
+        .compress(&buffer, buffer.len())
+        .unwrap();
+    let decompressed_buf = decompressor
+        .decompress(&compressed_buf, buffer.len())
+        .unwrap();
+
+    assert_eq!(decompressed_buf, buffer);
+    assert_eq!(
+        decompressor.decompress(&compressed_buf, buffer.len() - 1),
+        Err(DecompressError::InsufficientSpace)
+    );
+    assert_eq!(compressor.compress(&buffer, 4), None);
+}
@@ -1,69 +1,47 @@
+use std::sync::Arc;
+
 use crate::{
+    brotli::{
+        compressor::BrotliCompressor,
+        decompressor::BrotliDecompressor,
+    },
     deflate::{
         compressor::DeflateCompressor,
         decompressor::DeflateDecompressor,
     },
-    error::{
-        DecompressError,
-        DecompressResult,
+    error::DecompressResult,
+    lz4::{
+        compressor::Lz4Compressor,
+        decompressor::Lz4Decompressor,
+    },
+    traits::{
+        Compressor,
+        Decompressor,
     },
     zstd::{
         compressor::ZStdCctx,
         decompressor::ZStdDctx,
+        dictionary::ZStdDictionary,
+        stream::{
+            ZStdStreamCctx,
+            ZStdStreamDctx,
+        },
     },
 };
 
-pub enum BufCompressor {
-    ZStd(ZStdCctx),
-    Deflate(DeflateCompressor),
-}
+/// Compressor of any codec, see
+/// [`CompressionRegistry`](crate::registry::CompressionRegistry)
+/// for creating one by the algorithm id
+pub struct BufCompressor(Box<dyn Compressor>);
 
-pub enum BufDecompressor {
-    ZStd(ZStdDctx),
-    Deflate(DeflateDecompressor),
-}
+pub struct BufDecompressor(Box<dyn Decompressor>);
 
 impl BufDecompressor {
     pub fn decompress_unconstrained(
         &mut self,
         src: &[u8],
     ) -> DecompressResult<Vec<u8>> {
-        fn try_decompress(
-            src: &[u8],
-            max_size: usize,
-            decompressor: &mut BufDecompressor,
-        ) -> DecompressResult<Vec<u8>> {
-            match decompressor {
-                BufDecompressor::Deflate(deflate) => {
-                    deflate.decompress(src, max_size)
-                }
-
-                _ => unreachable!(),
-            }
-        }
-
-        #[allow(clippy::single_match)]
-        match self {
-            // ZStd decompressor will not allocate whole `usize::MAX` size,
-            // only needed size
-            Self::ZStd(zstd) => {
-                return zstd.decompress(src, usize::MAX);
-            }
-
-            _ => {}
-        }
-
-        let mut max_size = 4096_usize;
-        Ok(loop {
-            match try_decompress(src, max_size, self) {
-                Ok(b) => break b,
-                Err(DecompressError::InsufficientSpace) => {}
-                Err(e) => return Err(e),
-            }
-
-            // max_size *= 1.5
-            max_size = (max_size << 1) - (max_size >> 1);
-        })
+        self.0.decompress_unconstrained(src)
     }
 
     pub fn decompress_constrained(
@@ -71,18 +49,51 @@ impl BufDecompressor {
         src: &[u8],
         max_size: usize,
     ) -> DecompressResult<Vec<u8>> {
-        match self {
-            Self::ZStd(zstd) => zstd.decompress(src, max_size),
-            Self::Deflate(deflate) => deflate.decompress(src, max_size),
-        }
+        self.0.decompress(src, max_size)
+    }
+
+    /// See [`Decompressor::is_streaming`]
+    pub fn is_streaming(&self) -> bool {
+        self.0.is_streaming()
+    }
+
+    /// See [`Decompressor::max_compressed_size`]
+    pub fn max_compressed_size(&self, max_size: usize) -> usize {
+        self.0.max_compressed_size(max_size)
+    }
+
+    pub fn new(decompressor: impl Decompressor + 'static) -> Self {
+        Self(Box::new(decompressor))
     }
 
     pub fn deflate() -> Self {
-        Self::Deflate(DeflateDecompressor::new())
+        Self::new(DeflateDecompressor::new())
     }
 
     pub fn zstd() -> Self {
-        Self::ZStd(ZStdDctx::new())
+        Self::new(ZStdDctx::new())
+    }
+
+    pub fn lz4() -> Self {
+        Self::new(Lz4Decompressor::new())
+    }
+
+    pub fn brotli() -> Self {
+        Self::new(BrotliDecompressor::new())
+    }
+
+    pub fn zstd_stream() -> Self {
+        Self::new(ZStdStreamDctx::new())
+    }
+
+    pub fn zstd_with_dictionary(dictionary: Arc<ZStdDictionary>) -> Self {
+        Self::new(ZStdDctx::with_dictionary(dictionary))
+    }
+
+    pub fn zstd_stream_with_dictionary(
+        dictionary: Arc<ZStdDictionary>,
+    ) -> Self {
+        Self::new(ZStdStreamDctx::with_dictionary(dictionary))
     }
 }
 
@@ -92,20 +103,48 @@ impl BufCompressor {
         src: &[u8],
         max_size: usize,
     ) -> Option<Vec<u8>> {
-        match self {
-            Self::Deflate(deflate) => deflate.compress(src, max_size),
-            Self::ZStd(zstd) => zstd.compress(src, max_size),
-        }
+        self.0.compress(src, max_size)
+    }
+
+    /// See [`Compressor::is_streaming`]
+    pub fn is_streaming(&self) -> bool {
+        self.0.is_streaming()
+    }
+
+    pub fn new(compressor: impl Compressor + 'static) -> Self {
+        Self(Box::new(compressor))
     }
 
     pub fn deflate(level: u8) -> Self {
-        Self::Deflate(DeflateCompressor::new(level))
+        Self::new(DeflateCompressor::new(level))
     }
 
     pub fn zstd(level: u8) -> Self {
-        Self::ZStd(ZStdCctx::new(level))
+        Self::new(ZStdCctx::new(level))
+    }
+
+    /// LZ4 has no levels
+    pub fn lz4() -> Self {
+        Self::new(Lz4Compressor::new())
+    }
+
+    pub fn brotli(level: u8) -> Self {
+        Self::new(BrotliCompressor::new(level))
+    }
+
+    pub fn zstd_stream(level: u8) -> Self {
+        Self::new(ZStdStreamCctx::new(level))
     }
-}
 
-unsafe impl Send for BufCompressor {}
-unsafe impl Send for BufDecompressor {}
+    /// Level is taken from the dictionary
+    pub fn zstd_with_dictionary(dictionary: Arc<ZStdDictionary>) -> Self {
+        Self::new(ZStdCctx::with_dictionary(dictionary))
+    }
+
+    pub fn zstd_stream_with_dictionary(
+        level: u8,
+        dictionary: &ZStdDictionary,
+    ) -> Self {
+        Self::new(ZStdStreamCctx::with_dictionary(level, dictionary))
+    }
+}
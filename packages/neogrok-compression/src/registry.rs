@@ -0,0 +1,178 @@
+use std::{
+    collections::BTreeMap,
+    sync::OnceLock,
+};
+
+use crate::{
+    brotli::{
+        compressor::BrotliCompressor,
+        decompressor::BrotliDecompressor,
+    },
+    deflate::{
+        compressor::DeflateCompressor,
+        decompressor::DeflateDecompressor,
+    },
+    error::{
+        CodecError,
+        CodecResult,
+        CompressorInitResult,
+        DecompressorInitResult,
+    },
+    lz4::{
+        compressor::Lz4Compressor,
+        decompressor::Lz4Decompressor,
+    },
+    polymorphic::{
+        BufCompressor,
+        BufDecompressor,
+    },
+    zstd::{
+        compressor::ZStdCctx,
+        decompressor::ZStdDctx,
+    },
+};
+
+/// Algorithm id sent in the ping response, ids of the
+/// builtin codecs match the protocol's
+/// `CompressionAlgorithm`
+pub type AlgorithmId = u8;
+
+pub const DEFLATE: AlgorithmId = 0;
+pub const ZSTD: AlgorithmId = 1;
+pub const LZ4: AlgorithmId = 2;
+pub const BROTLI: AlgorithmId = 3;
+
+type CompressorFactory =
+    Box<dyn Fn(u8) -> CompressorInitResult<BufCompressor> + Send + Sync>;
+type DecompressorFactory =
+    Box<dyn Fn() -> DecompressorInitResult<BufDecompressor> + Send + Sync>;
+
+struct Codec {
+    name: &'static str,
+    compressor: CompressorFactory,
+    decompressor: DecompressorFactory,
+}
+
+/// Codecs keyed by the algorithm id, lets the downstream
+/// crates plug in their own codecs
+#[derive(Default)]
+pub struct CompressionRegistry {
+    codecs: BTreeMap<AlgorithmId, Codec>,
+}
+
+impl CompressionRegistry {
+    /// Replaces codec registered under the same `id`, so
+    /// the builtin ones can be overridden too
+    pub fn register<C, D>(
+        &mut self,
+        id: AlgorithmId,
+        name: &'static str,
+        compressor: C,
+        decompressor: D,
+    ) -> &mut Self
+    where
+        C: Fn(u8) -> CompressorInitResult<BufCompressor>
+            + Send
+            + Sync
+            + 'static,
+        D: Fn() -> DecompressorInitResult<BufDecompressor>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.codecs.insert(
+            id,
+            Codec {
+                name,
+                compressor: Box::new(compressor),
+                decompressor: Box::new(decompressor),
+            },
+        );
+        self
+    }
+
+    pub fn compressor(
+        &self,
+        id: AlgorithmId,
+        level: u8,
+    ) -> CodecResult<BufCompressor> {
+        let codec = self.codec(id)?;
+        Ok((codec.compressor)(level)?)
+    }
+
+    pub fn decompressor(
+        &self,
+        id: AlgorithmId,
+    ) -> CodecResult<BufDecompressor> {
+        let codec = self.codec(id)?;
+        Ok((codec.decompressor)()?)
+    }
+
+    pub fn pair(
+        &self,
+        id: AlgorithmId,
+        level: u8,
+    ) -> CodecResult<(BufCompressor, BufDecompressor)> {
+        Ok((self.compressor(id, level)?, self.decompressor(id)?))
+    }
+
+    pub fn name(&self, id: AlgorithmId) -> Option<&'static str> {
+        self.codecs.get(&id).map(|codec| codec.name)
+    }
+
+    pub fn contains(&self, id: AlgorithmId) -> bool {
+        self.codecs.contains_key(&id)
+    }
+
+    fn codec(&self, id: AlgorithmId) -> CodecResult<&Codec> {
+        self.codecs
+            .get(&id)
+            .ok_or(CodecError::UnknownAlgorithm(id))
+    }
+
+    /// Shared registry of the builtin codecs
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<CompressionRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(Self::with_builtin)
+    }
+
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::default();
+        registry
+            .register(
+                DEFLATE,
+                "deflate",
+                |level| {
+                    DeflateCompressor::try_new(level)
+                        .map(BufCompressor::new)
+                },
+                || {
+                    DeflateDecompressor::try_new()
+                        .map(BufDecompressor::new)
+                },
+            )
+            .register(
+                ZSTD,
+                "zstd",
+                |level| ZStdCctx::try_new(level).map(BufCompressor::new),
+                || ZStdDctx::try_new().map(BufDecompressor::new),
+            )
+            .register(
+                LZ4,
+                "lz4",
+                |_| Ok(BufCompressor::new(Lz4Compressor::new())),
+                || Ok(BufDecompressor::new(Lz4Decompressor::new())),
+            )
+            .register(
+                BROTLI,
+                "brotli",
+                |level| {
+                    BrotliCompressor::try_new(level)
+                        .map(BufCompressor::new)
+                },
+                || Ok(BufDecompressor::new(BrotliDecompressor::new())),
+            );
+
+        registry
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    error::{
+        CodecError,
+        DecompressError,
+    },
+    polymorphic::{
+        BufCompressor,
+        BufDecompressor,
+    },
+    registry::*,
+    traits::*,
+};
+
+/// Toy codec which reverses the buffer
+struct Reverse;
+
+impl Compressor for Reverse {
+    fn compress(
+        &mut self,
+        src: &[u8],
+        max_size: usize,
+    ) -> Option<Vec<u8>> {
+        (src.len() <= max_size)
+            .then(|| src.iter().rev().copied().collect())
+    }
+}
+
+impl Decompressor for Reverse {
+    fn decompress(
+        &mut self,
+        src: &[u8],
+        _max_size: usize,
+    ) -> crate::error::DecompressResult<Vec<u8>> {
+        Ok(src.iter().rev().copied().collect())
+    }
+}
+
+#[test]
+fn test_builtin_registry() {
+    let registry = CompressionRegistry::builtin();
+    let buffer = b"Hello world, guys. Hello world, guys!".repeat(4);
+
+    for id in [DEFLATE, ZSTD, LZ4, BROTLI] {
+        let (mut compressor, mut decompressor) =
+            registry.pair(id, 3).unwrap();
+        let compressed = compressor
+            .compress(&buffer, buffer.len())
+            .unwrap();
+
+        assert_eq!(
+            decompressor
+                .decompress_unconstrained(&compressed)
+                .unwrap(),
+            buffer,
+            "{:?}",
+            registry.name(id)
+        );
+    }
+
+    assert!(matches!(
+        registry.pair(200, 3),
+        Err(CodecError::UnknownAlgorithm(200))
+    ));
+    assert!(registry.compressor(DEFLATE, 13).is_err());
+}
+
+#[test]
+fn test_unconstrained_limit() {
+    let registry = CompressionRegistry::builtin();
+    // Deflate may write up to twice the requested limit
+    let buffer = vec![0_u8; MAX_UNCONSTRAINED_SIZE * 4];
+
+    for id in [DEFLATE, ZSTD, LZ4, BROTLI] {
+        let (mut compressor, mut decompressor) =
+            registry.pair(id, 3).unwrap();
+        let compressed = compressor
+            .compress(&buffer, buffer.len())
+            .unwrap();
+
+        assert!(
+            matches!(
+                decompressor.decompress_unconstrained(&compressed),
+                Err(DecompressError::InsufficientSpace)
+            ),
+            "{:?}",
+            registry.name(id)
+        );
+    }
+}
+
+#[test]
+fn test_custom_codec() {
+    let mut registry = CompressionRegistry::with_builtin();
+    registry.register(
+        200,
+        "reverse",
+        |_| Ok(BufCompressor::new(Reverse)),
+        || Ok(BufDecompressor::new(Reverse)),
+    );
+
+    let (mut compressor, mut decompressor) =
+        registry.pair(200, 0).unwrap();
+    let compressed = compressor.compress(b"abc", 3).unwrap();
+    assert_eq!(compressed, b"cba");
+    assert_eq!(
+        decompressor
+            .decompress_constrained(&compressed, 3)
+            .unwrap(),
+        b"abc"
+    );
+    assert_eq!(registry.name(200), Some("reverse"));
+    assert!(registry.contains(ZSTD));
+}
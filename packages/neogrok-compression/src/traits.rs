@@ -0,0 +1,70 @@
+use crate::error::{
+    DecompressError,
+    DecompressResult,
+};
+
+/// Output limit of the unconstrained decompression, no
+/// forwarded buffer is larger than this
+pub const MAX_UNCONSTRAINED_SIZE: usize = u16::MAX as usize;
+
+/// Compresses the forwarded buffers. Every session owns its
+/// compressor, so the state can be kept between the calls.
+pub trait Compressor: Send {
+    /// Returns `None` if the output doesn't fit in the
+    /// `max_size`, the buffer is sent as is then
+    fn compress(&mut self, src: &[u8], max_size: usize)
+        -> Option<Vec<u8>>;
+
+    /// Streaming compressors keep history between the
+    /// buffers, so every compressed buffer must be sent
+    fn is_streaming(&self) -> bool {
+        false
+    }
+}
+
+pub trait Decompressor: Send {
+    /// Fails with [`DecompressError::InsufficientSpace`] if
+    /// the output is larger than `max_size`
+    fn decompress(
+        &mut self,
+        src: &[u8],
+        max_size: usize,
+    ) -> DecompressResult<Vec<u8>>;
+
+    /// Fails with [`DecompressError::InsufficientSpace`] if
+    /// the output is larger than
+    /// [`MAX_UNCONSTRAINED_SIZE`]. Retries with the
+    /// growing limit by default, codecs which know the
+    /// output size should override it
+    fn decompress_unconstrained(
+        &mut self,
+        src: &[u8],
+    ) -> DecompressResult<Vec<u8>> {
+        let mut max_size = 4096_usize;
+        loop {
+            match self.decompress(src, max_size) {
+                Err(DecompressError::InsufficientSpace)
+                    if max_size < MAX_UNCONSTRAINED_SIZE => {}
+                result => break result,
+            }
+
+            // max_size *= 1.5
+            max_size = ((max_size << 1) - (max_size >> 1))
+                .min(MAX_UNCONSTRAINED_SIZE);
+        }
+    }
+
+    /// Streaming decompressors keep history between the
+    /// buffers, so the failed decompression can't be
+    /// retried
+    fn is_streaming(&self) -> bool {
+        false
+    }
+
+    /// Largest compressed buffer which can decompress to
+    /// at most `max_size` bytes. Only the streaming
+    /// compressors produce buffers larger than the source.
+    fn max_compressed_size(&self, max_size: usize) -> usize {
+        max_size
+    }
+}
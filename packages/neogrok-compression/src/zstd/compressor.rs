@@ -1,28 +1,36 @@
 use std::{
     ffi,
     ptr::NonNull,
+    sync::Arc,
 };
 
 use zstd_sys::{
     ZSTD_CCtx,
     ZSTD_compressCCtx,
+    ZSTD_compress_usingCDict,
     ZSTD_createCCtx,
     ZSTD_freeCCtx,
     ZSTD_isError,
 };
 
-use crate::error::{
-    CompressorInitError,
-    CompressorInitResult,
+use super::dictionary::ZStdDictionary;
+use crate::{
+    error::{
+        CompressorInitError,
+        CompressorInitResult,
+    },
+    traits::Compressor,
 };
 
 pub struct ZStdCctx {
     cctx: NonNull<ZSTD_CCtx>,
     level: u8,
+
+    dictionary: Option<Arc<ZStdDictionary>>,
 }
 
-impl ZStdCctx {
-    pub fn compress(
+impl Compressor for ZStdCctx {
+    fn compress(
         &mut self,
         buffer: &[u8],
         max_allocate_size: usize,
@@ -35,14 +43,25 @@ impl ZStdCctx {
                 let spare = out.spare_capacity_mut();
                 let out_ptr = spare.as_ptr() as *mut ffi::c_void;
 
-                result = ZSTD_compressCCtx(
-                    self.cctx.as_ptr(),
-                    out_ptr,
-                    out.capacity(),
-                    buffer.as_ptr() as *const _,
-                    buffer.len(),
-                    self.level as i32,
-                );
+                result = match &self.dictionary {
+                    // Level was set when the dictionary was prepared
+                    Some(dictionary) => ZSTD_compress_usingCDict(
+                        self.cctx.as_ptr(),
+                        out_ptr,
+                        out.capacity(),
+                        buffer.as_ptr() as *const _,
+                        buffer.len(),
+                        dictionary.cdict(),
+                    ),
+                    None => ZSTD_compressCCtx(
+                        self.cctx.as_ptr(),
+                        out_ptr,
+                        out.capacity(),
+                        buffer.as_ptr() as *const _,
+                        buffer.len(),
+                        self.level as i32,
+                    ),
+                };
             }
 
             if ZSTD_isError(result) == 1 {
@@ -53,14 +72,36 @@ impl ZStdCctx {
             }
         }
     }
+}
 
+impl ZStdCctx {
     pub fn try_new(level: u8) -> CompressorInitResult<Self> {
         let cctx = unsafe { ZSTD_createCCtx() };
         NonNull::new(cctx)
-            .map(|cctx| Self { cctx, level })
+            .map(|cctx| Self {
+                cctx,
+                level,
+                dictionary: None,
+            })
             .ok_or(CompressorInitError::FailedToAllocate)
     }
 
+    /// Compresses with the dictionary's level, the peer
+    /// must use the same dictionary to decompress
+    pub fn try_with_dictionary(
+        dictionary: Arc<ZStdDictionary>,
+    ) -> CompressorInitResult<Self> {
+        let mut cctx = Self::try_new(0)?;
+        cctx.dictionary = Some(dictionary);
+
+        Ok(cctx)
+    }
+
+    pub fn with_dictionary(dictionary: Arc<ZStdDictionary>) -> Self {
+        Self::try_with_dictionary(dictionary)
+            .expect("Failed to allocte ZStd compress context")
+    }
+
     pub fn new(level: u8) -> Self {
         Self::try_new(level)
             .expect("Failed to allocte ZStd compress context")
@@ -72,3 +113,7 @@ impl Drop for ZStdCctx {
         unsafe { ZSTD_freeCCtx(self.cctx.as_ptr()) };
     }
 }
+
+// zstd contexts may move between threads, they just
+// can't be used concurrently
+unsafe impl Send for ZStdCctx {}
@@ -1,30 +1,58 @@
 use std::{
     ffi,
     ptr::NonNull,
+    sync::Arc,
 };
 
 use zstd_sys::{
     ZSTD_DCtx,
     ZSTD_createDCtx,
     ZSTD_decompressDCtx,
+    ZSTD_decompress_usingDDict,
     ZSTD_freeDCtx,
     ZSTD_getDecompressedSize,
+    ZSTD_getDictID_fromFrame,
     ZSTD_isError,
 };
 
-use crate::error::{
-    DecompressError,
-    DecompressResult,
-    DecompressorError,
-    DecompressorInitResult,
+use super::dictionary::ZStdDictionary;
+use crate::{
+    error::{
+        DecompressError,
+        DecompressResult,
+        DecompressorError,
+        DecompressorInitResult,
+    },
+    traits::{
+        Decompressor,
+        MAX_UNCONSTRAINED_SIZE,
+    },
 };
 
+/// Fails with [`DecompressError::DictionaryMismatch`] if
+/// the frame's dictionary differs from `dictionary`, no
+/// dictionary is id zero
+pub(crate) fn check_dictionary(
+    frame: &[u8],
+    dictionary: Option<&ZStdDictionary>,
+) -> DecompressResult<()> {
+    let frame_id = unsafe {
+        ZSTD_getDictID_fromFrame(frame.as_ptr() as *const _, frame.len())
+    };
+    if frame_id == dictionary.map_or(0, ZStdDictionary::id) {
+        Ok(())
+    } else {
+        Err(DecompressError::DictionaryMismatch)
+    }
+}
+
 pub struct ZStdDctx {
     dctx: NonNull<ZSTD_DCtx>,
+    dictionary: Option<Arc<ZStdDictionary>>,
 }
 
-impl ZStdDctx {
-    pub fn decompress(
+impl Decompressor for ZStdDctx {
+    fn decompress(
         &mut self,
         in_buffer: &[u8],
         max_allocate_size: usize,
@@ -38,6 +66,7 @@ impl ZStdDctx {
         if size == 0 || size > max_allocate_size as u64 {
             return Err(DecompressError::InsufficientSpace);
         }
+        check_dictionary(in_buffer, self.dictionary.as_deref())?;
 
         unsafe {
             let mut buffer: Vec<u8> = Vec::with_capacity(size as usize);
@@ -46,13 +75,23 @@ impl ZStdDctx {
                 let spare = buffer.spare_capacity_mut();
                 let spare_ptr = spare.as_ptr() as *mut ffi::c_void;
 
-                result = ZSTD_decompressDCtx(
-                    self.dctx.as_ptr(),
-                    spare_ptr,
-                    buffer.capacity(),
-                    in_buffer.as_ptr() as *const _,
-                    in_buffer.len(),
-                );
+                result = match &self.dictionary {
+                    Some(dictionary) => ZSTD_decompress_usingDDict(
+                        self.dctx.as_ptr(),
+                        spare_ptr,
+                        buffer.capacity(),
+                        in_buffer.as_ptr() as *const _,
+                        in_buffer.len(),
+                        dictionary.ddict(),
+                    ),
+                    None => ZSTD_decompressDCtx(
+                        self.dctx.as_ptr(),
+                        spare_ptr,
+                        buffer.capacity(),
+                        in_buffer.as_ptr() as *const _,
+                        in_buffer.len(),
+                    ),
+                };
             }
 
             if ZSTD_isError(result) == 1 {
@@ -64,12 +103,40 @@ impl ZStdDctx {
         }
     }
 
+    /// Decompressed size is stored in the frame, only the
+    /// needed size is allocated
+    fn decompress_unconstrained(
+        &mut self,
+        src: &[u8],
+    ) -> DecompressResult<Vec<u8>> {
+        self.decompress(src, MAX_UNCONSTRAINED_SIZE)
+    }
+}
+
+impl ZStdDctx {
     pub fn try_new() -> DecompressorInitResult<Self> {
         NonNull::new(unsafe { ZSTD_createDCtx() })
-            .map(|dctx| Self { dctx })
+            .map(|dctx| Self {
+                dctx,
+                dictionary: None,
+            })
             .ok_or(DecompressorError::FailedToAllocate)
     }
 
+    pub fn try_with_dictionary(
+        dictionary: Arc<ZStdDictionary>,
+    ) -> DecompressorInitResult<Self> {
+        let mut dctx = Self::try_new()?;
+        dctx.dictionary = Some(dictionary);
+
+        Ok(dctx)
+    }
+
+    pub fn with_dictionary(dictionary: Arc<ZStdDictionary>) -> Self {
+        Self::try_with_dictionary(dictionary)
+            .expect("Failed to allocate ZStd decompression context")
+    }
+
     pub fn new() -> Self {
         Self::default()
     }
@@ -89,3 +156,6 @@ impl Drop for ZStdDctx {
         }
     }
 }
+
+// See the `ZStdCctx`
+unsafe impl Send for ZStdDctx {}
@@ -0,0 +1,143 @@
+use std::{
+    ffi,
+    fmt,
+    ptr::NonNull,
+};
+
+use zstd_sys::{
+    ZDICT_isError,
+    ZDICT_trainFromBuffer,
+    ZSTD_CDict,
+    ZSTD_DDict,
+    ZSTD_createCDict,
+    ZSTD_createDDict,
+    ZSTD_freeCDict,
+    ZSTD_freeDDict,
+    ZSTD_getDictID_fromDict,
+};
+
+use crate::error::{
+    DictionaryError,
+    DictionaryResult,
+};
+
+/// Size of the trained dictionary recommended by zstd
+pub const DEFAULT_DICTIONARY_SIZE: usize = 110 * 1024;
+
+/// Pre-trained zstd dictionary, prepared once and shared by
+/// all compression contexts. Both sides must use the same
+/// dictionary, they're told apart by the id.
+pub struct ZStdDictionary {
+    cdict: NonNull<ZSTD_CDict>,
+    ddict: NonNull<ZSTD_DDict>,
+
+    raw: Box<[u8]>,
+    id: u32,
+}
+
+impl ZStdDictionary {
+    /// Id stored in the dictionary, never zero
+    pub const fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub(crate) fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub(crate) fn cdict(&self) -> *const ZSTD_CDict {
+        self.cdict.as_ptr()
+    }
+
+    pub(crate) fn ddict(&self) -> *const ZSTD_DDict {
+        self.ddict.as_ptr()
+    }
+
+    /// Only dictionaries in the zstd format are accepted,
+    /// as raw content ones have no id to negotiate.
+    /// `level` is used by the non-streaming
+    /// compressors.
+    pub fn try_new(raw: Vec<u8>, level: u8) -> DictionaryResult<Self> {
+        let raw = raw.into_boxed_slice();
+        let ptr = raw.as_ptr() as *const ffi::c_void;
+
+        let id = unsafe { ZSTD_getDictID_fromDict(ptr, raw.len()) };
+        if id == 0 {
+            return Err(DictionaryError::InvalidDictionary);
+        }
+
+        let cdict = NonNull::new(unsafe {
+            ZSTD_createCDict(ptr, raw.len(), level as i32)
+        })
+        .ok_or(DictionaryError::FailedToAllocate)?;
+        let Some(ddict) =
+            NonNull::new(unsafe { ZSTD_createDDict(ptr, raw.len()) })
+        else {
+            unsafe { ZSTD_freeCDict(cdict.as_ptr()) };
+            return Err(DictionaryError::FailedToAllocate);
+        };
+
+        Ok(Self {
+            cdict,
+            ddict,
+            raw,
+            id,
+        })
+    }
+}
+
+impl fmt::Debug for ZStdDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZStdDictionary")
+            .field("id", &self.id)
+            .field("size", &self.raw.len())
+            .finish()
+    }
+}
+
+impl Drop for ZStdDictionary {
+    fn drop(&mut self) {
+        unsafe {
+            ZSTD_freeCDict(self.cdict.as_ptr());
+            ZSTD_freeDDict(self.ddict.as_ptr());
+        }
+    }
+}
+
+// Prepared dictionaries are read-only, zstd allows sharing
+// them between the threads
+unsafe impl Send for ZStdDictionary {}
+unsafe impl Sync for ZStdDictionary {}
+
+/// Trains the dictionary of at most `max_size` bytes. zstd
+/// recommends thousands of samples, about a hundred times
+/// larger than the dictionary in total.
+pub fn train_dictionary<S: AsRef<[u8]>>(
+    samples: &[S],
+    max_size: usize,
+) -> DictionaryResult<Vec<u8>> {
+    let sizes: Vec<usize> =
+        samples.iter().map(|s| s.as_ref().len()).collect();
+    let mut concatenated = Vec::with_capacity(sizes.iter().sum());
+    for sample in samples {
+        concatenated.extend_from_slice(sample.as_ref());
+    }
+
+    let mut dictionary: Vec<u8> = Vec::with_capacity(max_size);
+    unsafe {
+        let size = ZDICT_trainFromBuffer(
+            dictionary.as_mut_ptr() as *mut ffi::c_void,
+            dictionary.capacity(),
+            concatenated.as_ptr() as *const ffi::c_void,
+            sizes.as_ptr(),
+            sizes.len() as _,
+        );
+        if ZDICT_isError(size) == 1 {
+            return Err(DictionaryError::TrainingFailed);
+        }
+
+        dictionary.set_len(size);
+    }
+
+    Ok(dictionary)
+}
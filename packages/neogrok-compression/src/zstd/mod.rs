@@ -1,5 +1,7 @@
 pub mod compressor;
 pub mod decompressor;
+pub mod dictionary;
+pub mod stream;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,354 @@
+use std::{
+    ffi,
+    ptr::NonNull,
+    sync::Arc,
+};
+
+use zstd_sys::{
+    ZSTD_CCtx,
+    ZSTD_CCtx_loadDictionary,
+    ZSTD_CCtx_reset,
+    ZSTD_CCtx_setParameter,
+    ZSTD_DCtx,
+    ZSTD_DCtx_refDDict,
+    ZSTD_DCtx_setParameter,
+    ZSTD_EndDirective,
+    ZSTD_ResetDirective,
+    ZSTD_cParameter,
+    ZSTD_compressBound,
+    ZSTD_compressStream2,
+    ZSTD_createCCtx,
+    ZSTD_createDCtx,
+    ZSTD_dParameter,
+    ZSTD_decompressStream,
+    ZSTD_freeCCtx,
+    ZSTD_freeDCtx,
+    ZSTD_inBuffer,
+    ZSTD_isError,
+    ZSTD_outBuffer,
+};
+
+use super::{
+    decompressor::check_dictionary,
+    dictionary::ZStdDictionary,
+};
+use crate::{
+    error::{
+        CompressorInitError,
+        CompressorInitResult,
+        DecompressError,
+        DecompressResult,
+        DecompressorError,
+        DecompressorInitResult,
+    },
+    traits::{
+        Compressor,
+        Decompressor,
+        MAX_UNCONSTRAINED_SIZE,
+    },
+};
+
+/// History window of the stream, 256KiB. Decompressor
+/// refuses larger windows, so the peer can't make us
+/// allocate more.
+const STREAM_WINDOW_LOG: i32 = 18;
+
+/// Largest compressed size of the `size` bytes, compressed
+/// buffer can be larger than the source one
+pub fn compress_bound(size: usize) -> usize {
+    unsafe { ZSTD_compressBound(size) }
+}
+
+/// Streaming zstd compressor, history is kept across the
+/// compressed buffers. Every buffer is flushed, so it can
+/// be decompressed as soon as it's received.
+pub struct ZStdStreamCctx {
+    cctx: NonNull<ZSTD_CCtx>,
+}
+
+impl Compressor for ZStdStreamCctx {
+    /// Returns `None` without touching the stream if the
+    /// output may not fit in the `max_allocate_size`.
+    /// Output is returned even if it's larger than the
+    /// input: the peer must see every buffer fed to the
+    /// stream.
+    fn compress(
+        &mut self,
+        buffer: &[u8],
+        max_allocate_size: usize,
+    ) -> Option<Vec<u8>> {
+        let bound = compress_bound(buffer.len());
+        if bound > max_allocate_size {
+            return None;
+        }
+
+        let mut out: Vec<u8> = Vec::with_capacity(bound);
+        let mut input = ZSTD_inBuffer {
+            src: buffer.as_ptr() as *const ffi::c_void,
+            size: buffer.len(),
+            pos: 0,
+        };
+
+        unsafe {
+            loop {
+                let spare = out.spare_capacity_mut();
+                let mut output = ZSTD_outBuffer {
+                    dst: spare.as_mut_ptr() as *mut ffi::c_void,
+                    size: spare.len(),
+                    pos: 0,
+                };
+
+                let remaining = ZSTD_compressStream2(
+                    self.cctx.as_ptr(),
+                    &mut output,
+                    &mut input,
+                    ZSTD_EndDirective::ZSTD_e_flush,
+                );
+                if ZSTD_isError(remaining) == 1 {
+                    // Context is unusable after the error. Peer's
+                    // stream is desynchronized now, so it will fail
+                    // to decompress the next buffer.
+                    ZSTD_CCtx_reset(
+                        self.cctx.as_ptr(),
+                        ZSTD_ResetDirective::ZSTD_reset_session_only,
+                    );
+                    return None;
+                }
+
+                out.set_len(out.len() + output.pos);
+                if remaining == 0 {
+                    break Some(out);
+                }
+
+                out.reserve(remaining);
+            }
+        }
+    }
+
+    fn is_streaming(&self) -> bool {
+        true
+    }
+}
+
+impl ZStdStreamCctx {
+    pub fn try_new(level: u8) -> CompressorInitResult<Self> {
+        let cctx = NonNull::new(unsafe { ZSTD_createCCtx() })
+            .map(|cctx| Self { cctx })
+            .ok_or(CompressorInitError::FailedToAllocate)?;
+
+        let params = [
+            (ZSTD_cParameter::ZSTD_c_compressionLevel, level as i32),
+            (ZSTD_cParameter::ZSTD_c_windowLog, STREAM_WINDOW_LOG),
+            // Defaults of the high levels are meant for the large
+            // inputs and take tens of megabytes
+            (ZSTD_cParameter::ZSTD_c_hashLog, STREAM_WINDOW_LOG - 1),
+            (ZSTD_cParameter::ZSTD_c_chainLog, STREAM_WINDOW_LOG - 1),
+        ];
+        for (param, value) in params {
+            let result = unsafe {
+                ZSTD_CCtx_setParameter(cctx.cctx.as_ptr(), param, value)
+            };
+            if unsafe { ZSTD_isError(result) } == 1 {
+                return Err(CompressorInitError::InvalidCompressionLevel);
+            }
+        }
+
+        Ok(cctx)
+    }
+
+    /// Dictionary is copied and loaded with the stream's
+    /// parameters, so the window stays limited
+    pub fn try_with_dictionary(
+        level: u8,
+        dictionary: &ZStdDictionary,
+    ) -> CompressorInitResult<Self> {
+        let cctx = Self::try_new(level)?;
+        let raw = dictionary.raw();
+        let result = unsafe {
+            ZSTD_CCtx_loadDictionary(
+                cctx.cctx.as_ptr(),
+                raw.as_ptr() as *const ffi::c_void,
+                raw.len(),
+            )
+        };
+        if unsafe { ZSTD_isError(result) } == 1 {
+            return Err(CompressorInitError::FailedToAllocate);
+        }
+
+        Ok(cctx)
+    }
+
+    pub fn new(level: u8) -> Self {
+        Self::try_new(level)
+            .expect("Failed to allocate ZStd stream compress context")
+    }
+
+    pub fn with_dictionary(
+        level: u8,
+        dictionary: &ZStdDictionary,
+    ) -> Self {
+        Self::try_with_dictionary(level, dictionary)
+            .expect("Failed to allocate ZStd stream compress context")
+    }
+}
+
+impl Drop for ZStdStreamCctx {
+    fn drop(&mut self) {
+        unsafe { ZSTD_freeCCtx(self.cctx.as_ptr()) };
+    }
+}
+
+/// Streaming counterpart of the
+/// [`ZStdDctx`](super::decompressor::ZStdDctx), buffers
+/// must be decompressed in the order they were compressed.
+pub struct ZStdStreamDctx {
+    dctx: NonNull<ZSTD_DCtx>,
+
+    /// Referenced by the context, must outlive it
+    dictionary: Option<Arc<ZStdDictionary>>,
+    /// Frame header with the dictionary id comes in the
+    /// first buffer of the stream
+    started: bool,
+}
+
+impl Decompressor for ZStdStreamDctx {
+    /// Failed decompression leaves the stream broken, every
+    /// error should be treated as fatal
+    fn decompress(
+        &mut self,
+        in_buffer: &[u8],
+        max_allocate_size: usize,
+    ) -> DecompressResult<Vec<u8>> {
+        if !self.started {
+            check_dictionary(in_buffer, self.dictionary.as_deref())?;
+            self.started = true;
+        }
+
+        // One byte past the limit tells whether the output
+        // is exactly `max_allocate_size` long or longer
+        let limit = max_allocate_size.saturating_add(1);
+        let mut out: Vec<u8> =
+            Vec::with_capacity(limit.min(in_buffer.len().max(512) << 1));
+        let mut input = ZSTD_inBuffer {
+            src: in_buffer.as_ptr() as *const ffi::c_void,
+            size: in_buffer.len(),
+            pos: 0,
+        };
+
+        unsafe {
+            loop {
+                let available = out.capacity().min(limit) - out.len();
+                let spare = out.spare_capacity_mut();
+                let mut output = ZSTD_outBuffer {
+                    dst: spare.as_mut_ptr() as *mut ffi::c_void,
+                    size: available,
+                    pos: 0,
+                };
+
+                let result = ZSTD_decompressStream(
+                    self.dctx.as_ptr(),
+                    &mut output,
+                    &mut input,
+                );
+                if ZSTD_isError(result) == 1 {
+                    return Err(DecompressError::InvalidCompressedData);
+                }
+
+                out.set_len(out.len() + output.pos);
+                if out.len() > max_allocate_size {
+                    return Err(DecompressError::InsufficientSpace);
+                }
+                if input.pos == input.size && output.pos < output.size {
+                    break Ok(out);
+                }
+
+                out.reserve(out.capacity().min(limit - out.len()));
+            }
+        }
+    }
+
+    /// Output is grown while decoding, so the largest limit
+    /// is used right away
+    fn decompress_unconstrained(
+        &mut self,
+        src: &[u8],
+    ) -> DecompressResult<Vec<u8>> {
+        self.decompress(src, MAX_UNCONSTRAINED_SIZE)
+    }
+
+    fn is_streaming(&self) -> bool {
+        true
+    }
+
+    fn max_compressed_size(&self, max_size: usize) -> usize {
+        compress_bound(max_size)
+    }
+}
+
+impl ZStdStreamDctx {
+    pub fn try_new() -> DecompressorInitResult<Self> {
+        let dctx = NonNull::new(unsafe { ZSTD_createDCtx() })
+            .map(|dctx| Self {
+                dctx,
+                dictionary: None,
+                started: false,
+            })
+            .ok_or(DecompressorError::FailedToAllocate)?;
+
+        let result = unsafe {
+            ZSTD_DCtx_setParameter(
+                dctx.dctx.as_ptr(),
+                ZSTD_dParameter::ZSTD_d_windowLogMax,
+                STREAM_WINDOW_LOG,
+            )
+        };
+        if unsafe { ZSTD_isError(result) } == 1 {
+            return Err(DecompressorError::FailedToAllocate);
+        }
+
+        Ok(dctx)
+    }
+
+    pub fn try_with_dictionary(
+        dictionary: Arc<ZStdDictionary>,
+    ) -> DecompressorInitResult<Self> {
+        let mut dctx = Self::try_new()?;
+        let result = unsafe {
+            ZSTD_DCtx_refDDict(dctx.dctx.as_ptr(), dictionary.ddict())
+        };
+        if unsafe { ZSTD_isError(result) } == 1 {
+            return Err(DecompressorError::FailedToAllocate);
+        }
+        dctx.dictionary = Some(dictionary);
+
+        Ok(dctx)
+    }
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dictionary(dictionary: Arc<ZStdDictionary>) -> Self {
+        Self::try_with_dictionary(dictionary)
+            .expect("Failed to allocate ZStd stream decompress context")
+    }
+}
+
+impl Default for ZStdStreamDctx {
+    fn default() -> Self {
+        Self::try_new()
+            .expect("Failed to allocate ZStd stream decompress context")
+    }
+}
+
+impl Drop for ZStdStreamDctx {
+    fn drop(&mut self) {
+        unsafe {
+            ZSTD_freeDCtx(self.dctx.as_ptr());
+        }
+    }
+}
+
+// Stream belongs to one session and is never shared
+unsafe impl Send for ZStdStreamCctx {}
+unsafe impl Send for ZStdStreamDctx {}
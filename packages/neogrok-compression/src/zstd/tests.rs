@@ -1,8 +1,38 @@
+use std::sync::Arc;
+
 use super::{
     compressor::*,
     decompressor::*,
+    dictionary::*,
+    stream::*,
+};
+use crate::{
+    error::{
+        DecompressError,
+        DictionaryError,
+    },
+    traits::*,
 };
 
+fn samples(seed: u64) -> Vec<Vec<u8>> {
+    (0..2000_u64)
+        .map(|i| {
+            let id = i.wrapping_mul(seed) % 100_000;
+            format!(
+                "{{\"method\":\"GET\",\"path\":\"/api/v1/users/{id}\",\"\
+                 headers\":{{\"accept\":\"application/json\"}},\"id\":\
+                 {i}}}"
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+fn dictionary(seed: u64) -> Arc<ZStdDictionary> {
+    let raw = train_dictionary(&samples(seed), 4096).unwrap();
+    Arc::new(ZStdDictionary::try_new(raw, 3).unwrap())
+}
+
 #[test]
 fn test_compression() {
     let buffer: &'static [u8] = &[0; 1024];
@@ -17,3 +47,109 @@ fn test_compression() {
 
     assert_eq!(buffer, d_buf);
 }
+
+#[test]
+fn test_stream_compression() {
+    let message: &'static [u8] =
+        b"{\"method\":\"GET\",\"path\":\"/api/v1/status\",\"id\":1}";
+
+    let mut compressor = ZStdStreamCctx::new(3);
+    let mut decompressor = ZStdStreamDctx::new();
+
+    let mut sizes = Vec::new();
+    for _ in 0..4 {
+        let c_buf = compressor
+            .compress(message, u16::MAX as usize)
+            .unwrap();
+        let d_buf = decompressor
+            .decompress(&c_buf, message.len())
+            .unwrap();
+
+        assert_eq!(message, d_buf);
+        sizes.push(c_buf.len());
+    }
+
+    // History is shared, so repeated message is much smaller
+    assert!(sizes[1] < sizes[0] / 2);
+
+    // Nothing is fed to the stream if output may not fit
+    assert_eq!(compressor.compress(message, 8), None);
+    let c_buf = compressor
+        .compress(message, u16::MAX as usize)
+        .unwrap();
+    assert_eq!(
+        decompressor.decompress(&c_buf, message.len() - 1),
+        Err(DecompressError::InsufficientSpace)
+    );
+}
+
+#[test]
+fn test_dictionary_compression() {
+    let dictionary = dictionary(7919);
+    let message = &samples(31)[5];
+
+    let mut compressor = ZStdCctx::with_dictionary(dictionary.clone());
+    let mut decompressor = ZStdDctx::with_dictionary(dictionary.clone());
+    let c_buf = compressor
+        .compress(message, message.len())
+        .unwrap();
+    assert_eq!(
+        decompressor
+            .decompress(&c_buf, message.len())
+            .unwrap(),
+        *message
+    );
+
+    let plain = ZStdCctx::new(3)
+        .compress(message, compress_bound(message.len()))
+        .unwrap();
+    assert!(c_buf.len() < plain.len());
+
+    let mut compressor = ZStdStreamCctx::with_dictionary(3, &dictionary);
+    let mut decompressor = ZStdStreamDctx::with_dictionary(dictionary);
+    for _ in 0..2 {
+        let c_buf = compressor
+            .compress(message, u16::MAX as usize)
+            .unwrap();
+        assert_eq!(
+            decompressor
+                .decompress(&c_buf, message.len())
+                .unwrap(),
+            *message
+        );
+    }
+}
+
+#[test]
+fn test_dictionary_mismatch() {
+    let (ours, theirs) = (dictionary(7919), dictionary(104729));
+    assert_ne!(ours.id(), theirs.id());
+    let message = &samples(31)[5];
+
+    let c_buf = ZStdCctx::with_dictionary(theirs.clone())
+        .compress(message, message.len())
+        .unwrap();
+    assert_eq!(
+        ZStdDctx::with_dictionary(ours.clone())
+            .decompress(&c_buf, message.len()),
+        Err(DecompressError::DictionaryMismatch)
+    );
+    assert_eq!(
+        ZStdDctx::new().decompress(&c_buf, message.len()),
+        Err(DecompressError::DictionaryMismatch)
+    );
+
+    let c_buf = ZStdStreamCctx::new(3)
+        .compress(message, u16::MAX as usize)
+        .unwrap();
+    assert_eq!(
+        ZStdStreamDctx::with_dictionary(ours)
+            .decompress(&c_buf, message.len()),
+        Err(DecompressError::DictionaryMismatch)
+    );
+
+    assert_eq!(
+        ZStdDictionary::try_new(message.clone(), 3).unwrap_err(),
+        DictionaryError::InvalidDictionary
+    );
+}
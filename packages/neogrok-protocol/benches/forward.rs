@@ -0,0 +1,192 @@
+use std::{
+    alloc::{
+        GlobalAlloc,
+        Layout,
+        System,
+    },
+    future::Future,
+    num::NonZeroU16,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    },
+};
+
+use bytes::Bytes;
+use criterion::{
+    criterion_group,
+    criterion_main,
+    Criterion,
+    Throughput,
+};
+use neogrok_protocol::{
+    buffers::BufferPool,
+    compression::{
+        algorithms::polymorphic::{
+            BufCompressor,
+            BufDecompressor,
+        },
+        types::CompressionStrategy,
+    },
+    hisui::{
+        frame::Frame,
+        reader::HisuiReader,
+        writer::HisuiWriter,
+    },
+};
+use tokio::{
+    io::AsyncReadExt,
+    runtime::Runtime,
+};
+
+/// Forwarded per iteration
+const TRAFFIC: usize = 1 << 20;
+
+/// Default `server.buffer.per_client`
+const CHUNK_SIZE: usize = 1024;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+/// Prints allocations made by one run, criterion measures
+/// only the time
+fn report_allocations<F>(rt: &Runtime, name: &str, run: impl Fn() -> F)
+where
+    F: Future<Output = ()>,
+{
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    rt.block_on(run());
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    println!("{name}: {allocations} allocations per forwarded MiB");
+}
+
+/// Public client reads the traffic and the session writes
+/// it to the user
+async fn send(pooled: bool, traffic: &[u8]) {
+    let mut source = traffic;
+    let mut writer =
+        HisuiWriter::new(tokio::io::sink(), BufCompressor::deflate(1));
+    let mut pool = BufferPool::new(CHUNK_SIZE);
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        let buffer = if pooled {
+            match pool.read_from(&mut source).await.unwrap() {
+                0 => break,
+                _ => pool.split(),
+            }
+        } else {
+            // Copy of the read part, as it was done before
+            // the pool
+            match source.read(&mut chunk).await.unwrap() {
+                0 => break,
+                read => Bytes::from(Vec::from(&chunk[..read])),
+            }
+        };
+
+        writer
+            .write_forward(1, buffer, CompressionStrategy::Disable)
+            .await
+            .unwrap();
+    }
+}
+
+/// Session reads the frames sent by the user
+async fn receive(copied: bool, encoded: &[u8]) {
+    let mut reader =
+        HisuiReader::server(encoded, BufDecompressor::deflate());
+    let mut received = 0;
+
+    while received < TRAFFIC {
+        let Frame::Forward { buffer, .. } = reader
+            .read_frame_inconcurrent(NonZeroU16::new(CHUNK_SIZE as u16))
+            .await
+            .unwrap()
+        else {
+            unreachable!()
+        };
+
+        received += if copied {
+            // Reader allocated every payload before the codec
+            buffer.to_vec().len()
+        } else {
+            buffer.len()
+        };
+    }
+}
+
+fn bench_forward(c: &mut Criterion) {
+    let rt = runtime();
+    let traffic: Vec<u8> = (0..TRAFFIC).map(|i| i as u8).collect();
+    let encoded = rt.block_on(async {
+        let mut writer =
+            HisuiWriter::new(Vec::new(), BufCompressor::deflate(1));
+        for chunk in traffic.chunks(CHUNK_SIZE) {
+            writer
+                .write_forward(
+                    1,
+                    Bytes::copy_from_slice(chunk),
+                    CompressionStrategy::Disable,
+                )
+                .await
+                .unwrap();
+        }
+
+        writer.into_inner().0
+    });
+
+    let mut group = c.benchmark_group("forward");
+    group.throughput(Throughput::Bytes(TRAFFIC as u64));
+    for (name, flag) in [("copied", true), ("pooled", false)] {
+        let pooled = !flag;
+        report_allocations(&rt, &format!("send/{name}"), || {
+            send(pooled, &traffic)
+        });
+        group.bench_function(format!("send/{name}"), |b| {
+            b.iter(|| rt.block_on(send(pooled, &traffic)))
+        });
+
+        report_allocations(&rt, &format!("receive/{name}"), || {
+            receive(flag, &encoded)
+        });
+        group.bench_function(format!("receive/{name}"), |b| {
+            b.iter(|| rt.block_on(receive(flag, &encoded)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_forward);
+criterion_main!(benches);
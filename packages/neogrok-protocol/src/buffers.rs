@@ -0,0 +1,64 @@
+use std::io;
+
+use bytes::{
+    buf::Limit,
+    BufMut,
+    Bytes,
+    BytesMut,
+};
+use tokio::io::{
+    AsyncRead,
+    AsyncReadExt,
+};
+
+/// Chunks fitting in one allocation of the pool
+const CHUNKS_PER_ALLOCATION: usize = 4;
+
+/// Source of the forwarded payloads. Payloads are split off
+/// the shared allocation as refcounted [`Bytes`], and the
+/// allocation is reused once all of them are dropped, so
+/// the steady flow doesn't allocate at all. Allocation is
+/// replaced only while the receiving side lags behind.
+#[derive(Debug)]
+pub struct BufferPool {
+    buffer: BytesMut,
+    chunk_size: usize,
+}
+
+impl BufferPool {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            buffer: BytesMut::with_capacity(
+                chunk_size * CHUNKS_PER_ALLOCATION,
+            ),
+            chunk_size,
+        }
+    }
+
+    /// Room for at most `chunk_size` bytes, filled part is
+    /// taken by the [`Self::split`]
+    pub fn chunk(&mut self) -> Limit<&mut BytesMut> {
+        if self.buffer.capacity() - self.buffer.len() < self.chunk_size {
+            // Reclaims the allocation if no payload is alive
+            self.buffer
+                .reserve(self.chunk_size * CHUNKS_PER_ALLOCATION);
+        }
+
+        (&mut self.buffer).limit(self.chunk_size)
+    }
+
+    /// Reads at most `chunk_size` bytes, cancel safe
+    pub async fn read_from<Reader>(
+        &mut self,
+        reader: &mut Reader,
+    ) -> io::Result<usize>
+    where
+        Reader: AsyncRead + Unpin,
+    {
+        reader.read_buf(&mut self.chunk()).await
+    }
+
+    pub fn split(&mut self) -> Bytes {
+        self.buffer.split().freeze()
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use super::types::CompressionStatus;
+
+/// When to stop compressing the client's traffic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptivePolicy {
+    /// Compression is paused when the smoothed ratio of the
+    /// client drops below this value
+    pub min_ratio: f64,
+
+    /// Frames sent uncompressed after the first poor
+    /// result, doubled while the probes stay poor
+    pub skip_frames: u32,
+    pub max_skip_frames: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AdaptiveStats {
+    /// Buffers passed to the compressor
+    pub attempted: u64,
+
+    /// Buffers sent as is without trying to compress them
+    pub skipped: u64,
+}
+
+#[derive(Debug, Default)]
+struct ClientHistory {
+    /// `None` until the first result after the pause
+    ratio: Option<f64>,
+
+    skip: u32,
+    backoff: u32,
+}
+
+/// Tracks the compression ratio of every client, so
+/// incompressible traffic (TLS, media, archives) doesn't
+/// burn CPU on every frame. Paused clients are probed
+/// again after the backoff.
+#[derive(Debug, Default)]
+pub struct AdaptiveCompression {
+    clients: HashMap<u16, ClientHistory>,
+    stats: AdaptiveStats,
+}
+
+impl AdaptiveCompression {
+    /// Whether the client's buffer should be compressed,
+    /// counts the skipped attempt otherwise
+    pub fn should_compress(&mut self, id: u16) -> bool {
+        match self.clients.get_mut(&id) {
+            Some(history) if history.skip != 0 => {
+                history.skip -= 1;
+                self.stats.skipped += 1;
+                false
+            }
+
+            _ => true,
+        }
+    }
+
+    /// `status` is `None` if the output was not smaller
+    /// than the input, counts as no gain
+    pub fn record(
+        &mut self,
+        id: u16,
+        policy: &AdaptivePolicy,
+        status: Option<CompressionStatus>,
+    ) {
+        let sample = status.map_or(1.0, CompressionStatus::ratio);
+        let history = self.clients.entry(id).or_default();
+
+        // ratio = 3/4 * ratio + 1/4 * sample
+        let ratio = history
+            .ratio
+            .map_or(sample, |ratio| (ratio * 3.0 + sample) / 4.0);
+        if ratio >= policy.min_ratio {
+            history.ratio = Some(ratio);
+            history.backoff = 0;
+            return;
+        }
+
+        history.backoff = if history.backoff == 0 {
+            policy.skip_frames
+        } else {
+            history
+                .backoff
+                .saturating_mul(2)
+                .min(policy.max_skip_frames)
+        };
+        history.skip = history.backoff;
+        // Probe decides on its own, older results are stale
+        history.ratio = None;
+    }
+
+    pub fn record_attempt(&mut self) {
+        self.stats.attempted += 1;
+    }
+
+    /// History of the previous client with the same id is
+    /// irrelevant
+    pub fn forget(&mut self, id: u16) {
+        self.clients.remove(&id);
+    }
+
+    pub const fn stats(&self) -> AdaptiveStats {
+        self.stats
+    }
+}
@@ -1,3 +1,5 @@
+pub mod adaptive;
+pub mod offload;
 pub mod types;
 
 pub use neogrok_compression as algorithms;
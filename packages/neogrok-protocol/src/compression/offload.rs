@@ -0,0 +1,123 @@
+use std::{
+    io,
+    mem,
+    num::NonZeroUsize,
+    sync::Arc,
+};
+
+use neogrok_compression::{
+    error::{
+        DecompressError,
+        DecompressResult,
+    },
+    polymorphic::{
+        BufCompressor,
+        BufDecompressor,
+    },
+    traits::{
+        Compressor,
+        Decompressor,
+    },
+};
+use tokio::sync::Semaphore;
+
+/// Runs (de)compression of the large buffers on the
+/// blocking threads, so high levels don't stall the runtime
+/// workers. Every job is awaited by its session before the
+/// next frame, which keeps the frame order and the history
+/// of the streaming codecs intact.
+#[derive(Debug, Clone)]
+pub struct CompressionPool {
+    permits: Arc<Semaphore>,
+    min_size: usize,
+}
+
+/// Takes place of the codec while it's on the pool. Stays
+/// there only if the session was dropped mid-job.
+struct Detached;
+
+impl Compressor for Detached {
+    fn compress(&mut self, _: &[u8], _: usize) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+impl Decompressor for Detached {
+    fn decompress(
+        &mut self,
+        _: &[u8],
+        _: usize,
+    ) -> DecompressResult<Vec<u8>> {
+        Err(DecompressError::InvalidCompressedData)
+    }
+}
+
+impl CompressionPool {
+    /// Buffers shorter than `min_size` are processed in
+    /// place, offloading costs more than compressing them
+    pub fn new(parallelism: NonZeroUsize, min_size: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(parallelism.get())),
+            min_size,
+        }
+    }
+
+    pub const fn should_offload(&self, length: usize) -> bool {
+        length >= self.min_size
+    }
+
+    pub async fn compress<R, F>(
+        &self,
+        compressor: &mut BufCompressor,
+        job: F,
+    ) -> io::Result<R>
+    where
+        F: FnOnce(&mut BufCompressor) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.run(compressor, BufCompressor::new(Detached), job)
+            .await
+    }
+
+    pub async fn decompress<R, F>(
+        &self,
+        decompressor: &mut BufDecompressor,
+        job: F,
+    ) -> io::Result<R>
+    where
+        F: FnOnce(&mut BufDecompressor) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.run(decompressor, BufDecompressor::new(Detached), job)
+            .await
+    }
+
+    async fn run<C, R, F>(
+        &self,
+        codec: &mut C,
+        placeholder: C,
+        job: F,
+    ) -> io::Result<R>
+    where
+        C: Send + 'static,
+        F: FnOnce(&mut C) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(io::Error::other)?;
+
+        let mut owned = mem::replace(codec, placeholder);
+        let (owned, result) = tokio::task::spawn_blocking(move || {
+            let result = job(&mut owned);
+            (owned, result)
+        })
+        .await
+        .map_err(io::Error::other)?;
+        *codec = owned;
+
+        Ok(result)
+    }
+}
@@ -1,6 +1,17 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use super::adaptive::AdaptivePolicy;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CompressionStrategy {
-    TryCompress { with_threshold: u16 },
+    TryCompress {
+        with_threshold: u16,
+    },
+
+    /// Same as `TryCompress`, but compression of the client
+    /// is paused while it doesn't pay off
+    Adaptive {
+        with_threshold: u16,
+        policy: AdaptivePolicy,
+    },
     Disable,
 }
 
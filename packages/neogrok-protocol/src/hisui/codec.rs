@@ -0,0 +1,943 @@
+//! Sans-IO encoder and decoder of the hisui frames. They
+//! work on the [`BytesMut`] only, so can be used from the
+//! sync code, with [`tokio_util::codec::Framed`] or by the
+//! fuzzers. [`HisuiReader`](super::reader::HisuiReader) and
+//! [`HisuiWriter`](super::writer::HisuiWriter) are thin
+//! wrappers around them.
+
+use std::{
+    io,
+    net::{
+        IpAddr,
+        Ipv4Addr,
+        Ipv6Addr,
+        SocketAddr,
+    },
+    num::NonZeroU16,
+};
+
+use bytes::{
+    Buf,
+    Bytes,
+    BytesMut,
+};
+use common::protocol::{
+    error::ProtocolError,
+    types::*,
+};
+use ipnet::IpNet;
+use neogrok_compression::{
+    error::{
+        DecompressError,
+        DecompressResult,
+    },
+    polymorphic::{
+        BufCompressor,
+        BufDecompressor,
+    },
+};
+use tokio_util::codec::{
+    Decoder,
+    Encoder,
+};
+
+use super::{
+    codec_utils::{
+        encode_access_rule,
+        encode_client_header,
+        encode_connect_metadata,
+        encode_echo,
+        encode_fwd_header,
+        encode_request_server_header,
+        just_type,
+        ADDRESS_FAMILY_V4,
+        ADDRESS_FAMILY_V6,
+    },
+    error::ReadError,
+    frame::{
+        AccessAction,
+        Compression,
+        ConnectMetadata,
+        Frame,
+    },
+};
+use crate::compression::{
+    adaptive::{
+        AdaptiveCompression,
+        AdaptivePolicy,
+        AdaptiveStats,
+    },
+    types::{
+        CompressionStatus,
+        CompressionStrategy,
+    },
+};
+
+/// Decodes frames sent by the other side. Input is consumed
+/// only when the whole frame is available, or up to the
+/// invalid data when decoding fails.
+pub struct HisuiDecoder {
+    side: CodecSide,
+    max_fwd_buffer: Option<NonZeroU16>,
+
+    /// Payload of the rejected `Forward` frame left to
+    /// discard
+    skip: usize,
+
+    pub(crate) decompressor: BufDecompressor,
+}
+
+/// Encodes frames for the other side, compressing the
+/// `Forward` payloads
+pub struct HisuiEncoder {
+    strategy: CompressionStrategy,
+    adaptive: AdaptiveCompression,
+
+    pub(crate) compressor: BufCompressor,
+}
+
+/// [`HisuiDecoder`] and [`HisuiEncoder`] of the same
+/// connection, for use with
+/// [`Framed`](tokio_util::codec::Framed)
+pub struct HisuiCodec {
+    pub decoder: HisuiDecoder,
+    pub encoder: HisuiEncoder,
+}
+
+/// Decoded frame, `Forward` payload is left compressed so
+/// the caller can decompress it elsewhere
+pub(crate) enum Decoded {
+    Frame(Frame),
+    Compressed { id: u16, buffer: Bytes },
+}
+
+/// `Forward` payload is not copied out of the input, it's
+/// split off after the whole frame is checked
+enum Parsed {
+    Frame(Frame),
+    Forward {
+        id: u16,
+        length: usize,
+        compressed: bool,
+    },
+}
+
+/// Decision of the encoder about the `Forward` payload
+pub(crate) struct ForwardPlan {
+    /// `None` if the payload is sent as is
+    pub max_size: Option<usize>,
+    policy: Option<AdaptivePolicy>,
+}
+
+enum DecodeError {
+    /// Not enough input, `needed` more bytes are required
+    Incomplete {
+        needed: usize,
+    },
+    Invalid(ReadError),
+}
+
+impl From<ReadError> for DecodeError {
+    fn from(value: ReadError) -> Self {
+        Self::Invalid(value)
+    }
+}
+
+type DecodeResult<T> = Result<T, DecodeError>;
+
+struct Cursor<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    const fn new(input: &'a [u8]) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn take(&mut self, length: usize) -> DecodeResult<&'a [u8]> {
+        let end = self.position + length;
+        match self.input.get(self.position..end) {
+            Some(bytes) => {
+                self.position = end;
+                Ok(bytes)
+            }
+            None => Err(DecodeError::Incomplete {
+                needed: end - self.input.len(),
+            }),
+        }
+    }
+
+    fn array<const N: usize>(&mut self) -> DecodeResult<[u8; N]> {
+        self.take(N)
+            .map(|bytes| bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> DecodeResult<u8> {
+        self.array().map(|[byte]| byte)
+    }
+
+    fn u16_le(&mut self) -> DecodeResult<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32_le(&mut self) -> DecodeResult<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64_le(&mut self) -> DecodeResult<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    /// `SHORT`-like flag selects the one byte encoding
+    fn variadic(
+        &mut self,
+        flags: PacketFlags,
+        need: PacketFlags,
+    ) -> DecodeResult<u16> {
+        if flags.contains(need) {
+            self.u8().map(u16::from)
+        } else {
+            self.u16_le()
+        }
+    }
+
+    fn client_id(&mut self, flags: PacketFlags) -> DecodeResult<u16> {
+        self.variadic(flags, PacketFlags::SHORT2)
+    }
+
+    fn string_prefixed(&mut self) -> DecodeResult<String> {
+        let length = self.u8()? as usize;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| ReadError::InvalidString.into())
+    }
+
+    fn ip_address(&mut self) -> DecodeResult<IpAddr> {
+        Ok(match self.u8()? {
+            ADDRESS_FAMILY_V4 => Ipv4Addr::from(self.array::<4>()?).into(),
+            ADDRESS_FAMILY_V6 => {
+                Ipv6Addr::from(self.array::<16>()?).into()
+            }
+
+            family => {
+                return Err(
+                    ReadError::InvalidAddressFamily { family }.into()
+                )
+            }
+        })
+    }
+
+    fn connect_metadata(&mut self) -> DecodeResult<ConnectMetadata> {
+        let ip = self.ip_address()?;
+        let port = self.u16_le()?;
+
+        Ok(ConnectMetadata {
+            address: SocketAddr::new(ip, port),
+            listener_port: self.u16_le()?,
+            timestamp: self.u64_le()?,
+        })
+    }
+}
+
+pub(crate) fn decode_type(
+    data: u8,
+) -> Result<(u8, PacketFlags), ReadError> {
+    let flags = PacketFlags::from_bits(data & 0b111).ok_or(
+        ReadError::InvalidPacketFlags {
+            flags: data & 0b111,
+        },
+    )?;
+
+    Ok((data >> 3, flags))
+}
+
+pub(crate) fn decompress_payload(
+    decompressor: &mut BufDecompressor,
+    input: &[u8],
+    max_size: Option<usize>,
+) -> DecompressResult<Vec<u8>> {
+    let Some(max_size) = max_size else {
+        return decompressor.decompress_unconstrained(input);
+    };
+    if decompressor.is_streaming() {
+        return decompressor.decompress_constrained(input, max_size);
+    }
+
+    let length = input.len() << 1;
+
+    match decompressor.decompress_constrained(input, length) {
+        Ok(buffer) => Ok(buffer),
+        Err(DecompressError::InsufficientSpace) => {
+            decompressor.decompress_constrained(input, max_size)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+impl HisuiDecoder {
+    /// Decodes the packet type, the frame itself is decoded
+    /// by [`Self::decode_body`]
+    pub fn decode_header(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<(u8, PacketFlags)>, ReadError> {
+        if !self.discard_skipped(src) || src.is_empty() {
+            return Ok(None);
+        }
+
+        decode_type(src.get_u8()).map(Some)
+    }
+
+    /// Decodes the frame after its header was decoded by
+    /// [`Self::decode_header`]
+    pub fn decode_body(
+        &mut self,
+        pkt_type: u8,
+        flags: PacketFlags,
+        src: &mut BytesMut,
+    ) -> Result<Option<Frame>, ReadError> {
+        match self.decode_body_raw(pkt_type, flags, src)? {
+            Some(decoded) => self.finish(decoded).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Payload of the large `Forward` frames is
+    /// decompressed after the call, so it can be moved
+    /// to another thread
+    pub(crate) fn decode_body_raw(
+        &mut self,
+        pkt_type: u8,
+        flags: PacketFlags,
+        src: &mut BytesMut,
+    ) -> Result<Option<Decoded>, ReadError> {
+        self.decode_at(pkt_type, flags, src, 0)
+    }
+
+    pub(crate) fn finish(
+        &mut self,
+        decoded: Decoded,
+    ) -> Result<Frame, ReadError> {
+        let max_size = self.max_decompressed_size();
+        match decoded {
+            Decoded::Frame(frame) => Ok(frame),
+            Decoded::Compressed { id, buffer } => Ok(Frame::Forward {
+                id,
+                buffer: decompress_payload(
+                    &mut self.decompressor,
+                    &buffer,
+                    max_size,
+                )?
+                .into(),
+            }),
+        }
+    }
+
+    /// Limits the decoded `Forward` payload, longer ones
+    /// fail with the [`ReadError::TooLongBuffer`] and
+    /// are skipped
+    pub fn set_max_fwd_buffer(&mut self, max: Option<NonZeroU16>) {
+        self.max_fwd_buffer = max;
+    }
+
+    pub(crate) fn max_decompressed_size(&self) -> Option<usize> {
+        self.max_fwd_buffer.map(|max| max.get() as usize)
+    }
+
+    /// Returns whether the rejected payload is fully
+    /// discarded
+    fn discard_skipped(&mut self, src: &mut BytesMut) -> bool {
+        let discard = self.skip.min(src.len());
+        src.advance(discard);
+        self.skip -= discard;
+
+        self.skip == 0
+    }
+
+    /// Decodes the frame starting at `offset`, input is
+    /// consumed together with the first `offset` bytes
+    fn decode_at(
+        &mut self,
+        pkt_type: u8,
+        flags: PacketFlags,
+        src: &mut BytesMut,
+        offset: usize,
+    ) -> Result<Option<Decoded>, ReadError> {
+        let mut cursor = Cursor::new(&src[offset..]);
+        let result = self.decode_frame(pkt_type, flags, &mut cursor);
+        let consumed = offset + cursor.position;
+
+        match result {
+            Ok(Parsed::Frame(frame)) => {
+                src.advance(consumed);
+                Ok(Some(Decoded::Frame(frame)))
+            }
+
+            Ok(Parsed::Forward {
+                id,
+                length,
+                compressed,
+            }) => {
+                // Payload ends the frame
+                src.advance(consumed - length);
+                let buffer = src.split_to(length).freeze();
+                Ok(Some(if compressed {
+                    Decoded::Compressed { id, buffer }
+                } else {
+                    Decoded::Frame(Frame::Forward { id, buffer })
+                }))
+            }
+
+            Err(DecodeError::Incomplete { needed }) => {
+                src.reserve(needed);
+                Ok(None)
+            }
+
+            Err(DecodeError::Invalid(error)) => {
+                src.advance(consumed);
+                Err(error)
+            }
+        }
+    }
+
+    fn decode_frame(
+        &mut self,
+        pkt_type: u8,
+        flags: PacketFlags,
+        cursor: &mut Cursor<'_>,
+    ) -> DecodeResult<Parsed> {
+        Ok(Parsed::Frame(match pkt_type {
+            Frame::SERVER if self.side == CodecSide::Server => {
+                let protocol = if flags.contains(PacketFlags::COMPRESSED) {
+                    Protocol::Udp
+                } else if flags.contains(PacketFlags::SHORT2) {
+                    Protocol::Tcp
+                } else {
+                    return Err(ReadError::InvalidProtocol.into());
+                };
+                let port = if flags.contains(PacketFlags::SHORT) {
+                    0
+                } else {
+                    cursor.u16_le()?
+                };
+
+                Frame::ServerRequest { port, protocol }
+            }
+            Frame::SERVER if self.side == CodecSide::Client => {
+                Frame::ServerResponse {
+                    port: cursor.u16_le()?,
+                }
+            }
+
+            Frame::UPDATE_RIGHTS => {
+                let rights = cursor.u8()?;
+                Frame::UpdateRights {
+                    new_rights: Rights::from_bits(rights)
+                        .ok_or(ReadError::InvalidRights { rights })?,
+                }
+            }
+
+            Frame::PING if self.side == CodecSide::Server => {
+                Frame::PingRequest
+            }
+            Frame::PING if self.side == CodecSide::Client => {
+                Frame::PingResponse {
+                    compression: Compression {
+                        algorithm: cursor.u8()?,
+                        level: cursor.u8()?,
+                    },
+                    buffer_size: cursor.u16_le()?,
+                    server_name: cursor.string_prefixed()?,
+                }
+            }
+
+            Frame::CAPABILITIES => Frame::Capabilities {
+                // Unknown capabilities are just not supported by this
+                // side
+                capabilities: Capabilities::from_bits_truncate(
+                    cursor.u8()?,
+                ),
+            },
+
+            Frame::CONNECT => Frame::Connect {
+                id: cursor.client_id(flags)?,
+                metadata: if flags.contains(PacketFlags::SHORT) {
+                    Some(cursor.connect_metadata()?)
+                } else {
+                    None
+                },
+            },
+
+            Frame::FORWARD => {
+                let id = cursor.client_id(flags)?;
+                let length =
+                    cursor.variadic(flags, PacketFlags::SHORT)? as usize;
+                let compressed = flags.contains(PacketFlags::COMPRESSED);
+
+                if let Some(max_length) = self.max_decompressed_size() {
+                    let max_length = if compressed {
+                        self.decompressor.max_compressed_size(max_length)
+                    } else {
+                        max_length
+                    };
+
+                    if length > max_length {
+                        self.skip = length;
+                        return Err(ReadError::TooLongBuffer.into());
+                    }
+                }
+
+                cursor.take(length)?;
+                return Ok(Parsed::Forward {
+                    id,
+                    length,
+                    compressed,
+                });
+            }
+
+            Frame::DISCONNECT => Frame::Disconnect {
+                id: cursor.client_id(flags)?,
+            },
+
+            Frame::AUTH_MAGIC => Frame::AuthThroughMagic {
+                magic: cursor.string_prefixed()?,
+            },
+
+            Frame::ECHO => {
+                let nonce = cursor.u32_le()?;
+                let timestamp = cursor.u64_le()?;
+                if flags.contains(PacketFlags::SHORT) {
+                    Frame::EchoResponse { nonce, timestamp }
+                } else {
+                    Frame::EchoRequest { nonce, timestamp }
+                }
+            }
+
+            Frame::DICTIONARY => Frame::Dictionary {
+                id: cursor.u32_le()?,
+            },
+
+            Frame::SHUTDOWN => Frame::ShutdownNotice {
+                drain_timeout: cursor.u16_le()?,
+            },
+
+            Frame::ACCESS_RULE => {
+                let action = if flags.contains(PacketFlags::SHORT) {
+                    AccessAction::Deny
+                } else {
+                    AccessAction::Allow
+                };
+                let address = cursor.ip_address()?;
+                let prefix_len = cursor.u8()?;
+
+                Frame::AccessRule {
+                    action,
+                    network: IpNet::new(address, prefix_len).map_err(
+                        |_| ReadError::InvalidPrefixLength { prefix_len },
+                    )?,
+                }
+            }
+
+            Frame::ERROR => {
+                let code = cursor.u8()?;
+                Frame::Error(
+                    ProtocolError::try_from(code).map_err(|()| {
+                        ReadError::InvalidErrorCode { code }
+                    })?,
+                )
+            }
+
+            _ => {
+                return Err(ReadError::InvalidPacketType {
+                    pkt_type,
+                    flags,
+                }
+                .into())
+            }
+        }))
+    }
+
+    pub fn server(decompressor: BufDecompressor) -> Self {
+        Self::new(CodecSide::Server, decompressor)
+    }
+
+    pub fn client(decompressor: BufDecompressor) -> Self {
+        Self::new(CodecSide::Client, decompressor)
+    }
+
+    pub fn new(side: CodecSide, decompressor: BufDecompressor) -> Self {
+        Self {
+            side,
+            max_fwd_buffer: None,
+            skip: 0,
+            decompressor,
+        }
+    }
+}
+
+impl Decoder for HisuiDecoder {
+    type Error = ReadError;
+    type Item = Frame;
+
+    fn decode(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<Frame>, ReadError> {
+        if !self.discard_skipped(src) {
+            return Ok(None);
+        }
+        let Some(&data) = src.first() else {
+            return Ok(None);
+        };
+
+        let (pkt_type, flags) = match decode_type(data) {
+            Ok(header) => header,
+            Err(error) => {
+                src.advance(1);
+                return Err(error);
+            }
+        };
+
+        match self.decode_at(pkt_type, flags, src, 1)? {
+            Some(decoded) => self.finish(decoded).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Stream must end at the frame boundary, truncated
+    /// frame fails with [`io::ErrorKind::UnexpectedEof`]
+    fn decode_eof(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<Frame>, ReadError> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() && self.skip == 0 => Ok(None),
+            None => {
+                Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+            }
+        }
+    }
+}
+
+impl HisuiEncoder {
+    pub fn encode_forward(
+        &mut self,
+        id: u16,
+        buffer: &[u8],
+        strategy: CompressionStrategy,
+        dst: &mut BytesMut,
+    ) -> Option<CompressionStatus> {
+        let plan = self.plan_forward(id, buffer.len(), strategy);
+        let compressed = plan.max_size.and_then(|max_size| {
+            self.compressor.compress(buffer, max_size)
+        });
+        let status = self.encode_forward_header(
+            id,
+            buffer.len(),
+            compressed.as_deref(),
+            plan,
+            dst,
+        );
+
+        dst.extend_from_slice(compressed.as_deref().unwrap_or(buffer));
+        status
+    }
+
+    /// Decides whether the payload of `length` bytes should
+    /// be compressed, counts the attempt
+    pub(crate) fn plan_forward(
+        &mut self,
+        id: u16,
+        length: usize,
+        strategy: CompressionStrategy,
+    ) -> ForwardPlan {
+        let (with_threshold, policy) = match strategy {
+            CompressionStrategy::TryCompress { with_threshold } => {
+                (Some(with_threshold), None)
+            }
+            CompressionStrategy::Adaptive {
+                with_threshold,
+                policy,
+            } => (Some(with_threshold), Some(policy)),
+            CompressionStrategy::Disable => (None, None),
+        };
+
+        let attempt = with_threshold
+            .is_some_and(|threshold| (length as u16) >= threshold)
+            && (policy.is_none() || self.adaptive.should_compress(id));
+        if !attempt {
+            return ForwardPlan {
+                max_size: None,
+                policy: None,
+            };
+        }
+
+        self.adaptive.record_attempt();
+        ForwardPlan {
+            // Output of the streaming compressor is always sent,
+            // it only needs to fit in the frame
+            max_size: Some(if self.compressor.is_streaming() {
+                u16::MAX as usize
+            } else {
+                length
+            }),
+            policy,
+        }
+    }
+
+    /// Encodes header of the frame, which must be followed
+    /// by the `compressed` payload or by the original one
+    /// of `length` bytes. `compressed` is the output of
+    /// the compressor if it was smaller than the
+    /// `max_size`.
+    pub(crate) fn encode_forward_header(
+        &mut self,
+        id: u16,
+        length: usize,
+        compressed: Option<&[u8]>,
+        plan: ForwardPlan,
+        dst: &mut BytesMut,
+    ) -> Option<CompressionStatus> {
+        let status = compressed.map(|c| CompressionStatus {
+            before: length as _,
+            after: c.len() as _,
+        });
+        if let Some(policy) = plan.policy {
+            self.adaptive.record(id, &policy, status);
+        }
+
+        let payload_length = compressed.map_or(length, <[u8]>::len);
+        let (hdr, hdr_length) =
+            encode_fwd_header(id, payload_length as _, status.is_some());
+        dst.extend_from_slice(&hdr[..hdr_length]);
+
+        status
+    }
+
+    /// History of the previous client with the same id is
+    /// irrelevant for the adaptive strategy
+    pub(crate) fn forget_client(&mut self, id: u16) {
+        self.adaptive.forget(id);
+    }
+
+    /// Compression attempts made and skipped by the
+    /// adaptive strategy
+    pub const fn compression_stats(&self) -> AdaptiveStats {
+        self.adaptive.stats()
+    }
+
+    /// Strategy of the `Forward` frames encoded through the
+    /// [`Encoder`] trait
+    pub fn set_strategy(&mut self, strategy: CompressionStrategy) {
+        self.strategy = strategy;
+    }
+
+    pub fn new(compressor: BufCompressor) -> Self {
+        Self {
+            strategy: CompressionStrategy::Disable,
+            adaptive: AdaptiveCompression::default(),
+            compressor,
+        }
+    }
+}
+
+fn encode_prefixed(
+    header: &[u8],
+    string: &str,
+    dst: &mut BytesMut,
+) -> io::Result<()> {
+    let length = u8::try_from(string.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "string is longer than 255 bytes",
+        )
+    })?;
+
+    dst.reserve(header.len() + 1 + string.len());
+    dst.extend_from_slice(header);
+    dst.extend_from_slice(&[length]);
+    dst.extend_from_slice(string.as_bytes());
+
+    Ok(())
+}
+
+impl Encoder<Frame> for HisuiEncoder {
+    type Error = io::Error;
+
+    fn encode(
+        &mut self,
+        frame: Frame,
+        dst: &mut BytesMut,
+    ) -> io::Result<()> {
+        match frame {
+            Frame::ServerRequest { port, protocol } => {
+                let (hdr, len) =
+                    encode_request_server_header(port, protocol);
+                dst.extend_from_slice(&hdr[..len]);
+            }
+            Frame::ServerResponse { port } => {
+                let [lo, hi] = port.to_le_bytes();
+                dst.extend_from_slice(&[just_type(Frame::SERVER), lo, hi]);
+            }
+
+            Frame::PingRequest => {
+                dst.extend_from_slice(&[just_type(Frame::PING)]);
+            }
+            Frame::PingResponse {
+                server_name,
+                buffer_size,
+                compression,
+            } => {
+                let [lo, hi] = buffer_size.to_le_bytes();
+                encode_prefixed(
+                    &[
+                        just_type(Frame::PING),
+                        compression.algorithm,
+                        compression.level,
+                        lo,
+                        hi,
+                    ],
+                    &server_name,
+                    dst,
+                )?;
+            }
+
+            Frame::UpdateRights { new_rights } => {
+                dst.extend_from_slice(&[
+                    just_type(Frame::UPDATE_RIGHTS),
+                    new_rights.bits(),
+                ]);
+            }
+            Frame::Error(error) => {
+                dst.extend_from_slice(&[
+                    just_type(Frame::ERROR),
+                    error as _,
+                ]);
+            }
+
+            Frame::Capabilities { capabilities } => {
+                dst.extend_from_slice(&[
+                    just_type(Frame::CAPABILITIES),
+                    capabilities.bits(),
+                ]);
+            }
+
+            Frame::Connect { id, metadata } => {
+                self.forget_client(id);
+                let (mut hdr, hdr_len) =
+                    encode_client_header(Frame::CONNECT, id);
+                match metadata {
+                    Some(metadata) => {
+                        let (meta, meta_len) =
+                            encode_connect_metadata(&metadata);
+
+                        // `SHORT` flag on the `Connect` frame marks
+                        // attached metadata
+                        hdr[0] |= PacketFlags::SHORT.bits();
+                        dst.extend_from_slice(&hdr[..hdr_len]);
+                        dst.extend_from_slice(&meta[..meta_len]);
+                    }
+                    None => dst.extend_from_slice(&hdr[..hdr_len]),
+                }
+            }
+            Frame::Forward { id, buffer } => {
+                self.encode_forward(id, &buffer, self.strategy, dst);
+            }
+            Frame::Disconnect { id } => {
+                self.forget_client(id);
+                let (hdr, len) =
+                    encode_client_header(Frame::DISCONNECT, id);
+                dst.extend_from_slice(&hdr[..len]);
+            }
+
+            Frame::AuthThroughMagic { magic } => {
+                encode_prefixed(
+                    &[just_type(Frame::AUTH_MAGIC)],
+                    &magic,
+                    dst,
+                )?;
+            }
+
+            Frame::ShutdownNotice { drain_timeout } => {
+                let [lo, hi] = drain_timeout.to_le_bytes();
+                dst.extend_from_slice(&[
+                    just_type(Frame::SHUTDOWN),
+                    lo,
+                    hi,
+                ]);
+            }
+
+            Frame::AccessRule { action, network } => {
+                let (buf, len) = encode_access_rule(action, &network);
+                dst.extend_from_slice(&buf[..len]);
+            }
+
+            Frame::EchoRequest { nonce, timestamp } => {
+                dst.extend_from_slice(&encode_echo(
+                    PacketFlags::empty(),
+                    nonce,
+                    timestamp,
+                ));
+            }
+            Frame::EchoResponse { nonce, timestamp } => {
+                dst.extend_from_slice(&encode_echo(
+                    PacketFlags::SHORT,
+                    nonce,
+                    timestamp,
+                ));
+            }
+
+            Frame::Dictionary { id } => {
+                let [b0, b1, b2, b3] = id.to_le_bytes();
+                dst.extend_from_slice(&[
+                    just_type(Frame::DICTIONARY),
+                    b0,
+                    b1,
+                    b2,
+                    b3,
+                ]);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl HisuiCodec {
+    pub fn new(decoder: HisuiDecoder, encoder: HisuiEncoder) -> Self {
+        Self { decoder, encoder }
+    }
+}
+
+impl Decoder for HisuiCodec {
+    type Error = ReadError;
+    type Item = Frame;
+
+    fn decode(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<Frame>, ReadError> {
+        self.decoder.decode(src)
+    }
+
+    fn decode_eof(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<Frame>, ReadError> {
+        self.decoder.decode_eof(src)
+    }
+}
+
+impl Encoder<Frame> for HisuiCodec {
+    type Error = io::Error;
+
+    fn encode(
+        &mut self,
+        frame: Frame,
+        dst: &mut BytesMut,
+    ) -> io::Result<()> {
+        self.encoder.encode(frame, dst)
+    }
+}
@@ -1,6 +1,25 @@
+use std::net::IpAddr;
+
 use common::protocol::types::*;
+use ipnet::IpNet;
+
+use super::frame::{
+    AccessAction,
+    ConnectMetadata,
+    Frame,
+};
+
+/// Maximum size of the encoded connect metadata: address
+/// family, IPv6 address, remote port, listener port and
+/// timestamp
+pub(crate) const CONNECT_METADATA_MAX_SIZE: usize = 1 + 16 + 2 + 2 + 8;
 
-use super::frame::Frame;
+/// Maximum size of the encoded access rule: packet type,
+/// address family, IPv6 address and prefix length
+pub(crate) const ACCESS_RULE_MAX_SIZE: usize = 1 + 1 + 16 + 1;
+
+pub(crate) const ADDRESS_FAMILY_V4: u8 = 4;
+pub(crate) const ADDRESS_FAMILY_V6: u8 = 6;
 
 pub(crate) fn encode_request_server_header(
     port: u16,
@@ -45,6 +64,70 @@ pub(crate) const fn encode_client_header(
     }
 }
 
+pub(crate) fn encode_connect_metadata(
+    metadata: &ConnectMetadata,
+) -> ([u8; CONNECT_METADATA_MAX_SIZE], usize) {
+    let mut buf = [0_u8; CONNECT_METADATA_MAX_SIZE];
+    let offset = encode_ip_address(metadata.address.ip(), &mut buf);
+
+    buf[offset..offset + 2]
+        .copy_from_slice(&metadata.address.port().to_le_bytes());
+    buf[offset + 2..offset + 4]
+        .copy_from_slice(&metadata.listener_port.to_le_bytes());
+    buf[offset + 4..offset + 12]
+        .copy_from_slice(&metadata.timestamp.to_le_bytes());
+
+    (buf, offset + 12)
+}
+
+pub(crate) fn encode_access_rule(
+    action: AccessAction,
+    network: &IpNet,
+) -> ([u8; ACCESS_RULE_MAX_SIZE], usize) {
+    let mut buf = [0_u8; ACCESS_RULE_MAX_SIZE];
+    let flags = match action {
+        AccessAction::Allow => PacketFlags::empty(),
+        AccessAction::Deny => PacketFlags::SHORT,
+    };
+
+    buf[0] = encode_type(Frame::ACCESS_RULE, flags);
+    let offset = 1 + encode_ip_address(network.addr(), &mut buf[1..]);
+    buf[offset] = network.prefix_len();
+
+    (buf, offset + 1)
+}
+
+/// Echo request or response, depending on the `flags`
+pub(crate) fn encode_echo(
+    flags: PacketFlags,
+    nonce: u32,
+    timestamp: u64,
+) -> [u8; 13] {
+    let mut buf = [0; 13];
+    buf[0] = encode_type(Frame::ECHO, flags);
+    buf[1..5].copy_from_slice(&nonce.to_le_bytes());
+    buf[5..].copy_from_slice(&timestamp.to_le_bytes());
+
+    buf
+}
+
+/// Writes address family followed by the address octets,
+/// returns number of written bytes
+pub(crate) fn encode_ip_address(address: IpAddr, out: &mut [u8]) -> usize {
+    match address {
+        IpAddr::V4(ip) => {
+            out[0] = ADDRESS_FAMILY_V4;
+            out[1..5].copy_from_slice(&ip.octets());
+            5
+        }
+        IpAddr::V6(ip) => {
+            out[0] = ADDRESS_FAMILY_V6;
+            out[1..17].copy_from_slice(&ip.octets());
+            17
+        }
+    }
+}
+
 pub(crate) fn encode_fwd_header(
     id: u16,
     length: u16,
@@ -21,15 +21,24 @@ pub enum ReadError {
     #[error("invalid error code: 0x{code:x}")]
     InvalidErrorCode { code: u8 },
 
-    #[error("failed to decompress forward payload")]
-    FailedToDecompress(#[from] DecompressError),
+    #[error("failed to decompress forward payload: {0}")]
+    FailedToDecompress(DecompressError),
 
-    #[error("failed to read compression details")]
-    FailedToReadCompressionDetails,
+    #[error(
+        "forward payload was compressed with a different dictionary, \
+         dictionaries must be negotiated first"
+    )]
+    DictionaryMismatch,
 
     #[error("invalid rights: 0x{rights:x}")]
     InvalidRights { rights: u8 },
 
+    #[error("invalid address family: {family}")]
+    InvalidAddressFamily { family: u8 },
+
+    #[error("invalid network prefix length: {prefix_len}")]
+    InvalidPrefixLength { prefix_len: u8 },
+
     #[error("invalid network protocol")]
     InvalidProtocol,
 
@@ -37,6 +46,17 @@ pub enum ReadError {
     TooLongBuffer,
 }
 
+impl From<DecompressError> for ReadError {
+    fn from(value: DecompressError) -> Self {
+        match value {
+            DecompressError::DictionaryMismatch => {
+                Self::DictionaryMismatch
+            }
+            e => Self::FailedToDecompress(e),
+        }
+    }
+}
+
 impl From<io::Error> for ReadError {
     fn from(value: io::Error) -> Self {
         Self::Io(value)
@@ -1,7 +1,12 @@
+use std::net::SocketAddr;
+
+use bytes::Bytes;
 use common::protocol::{
     error::ProtocolError,
     types::*,
 };
+use ipnet::IpNet;
+use neogrok_compression::registry::AlgorithmId;
 
 macro_rules! impl_variants {
     (impl $frame:ident { $(const $id:ident = $expr:expr;)* }) => {
@@ -16,7 +21,29 @@ macro_rules! impl_variants {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Compression {
     pub level: u8,
-    pub algorithm: CompressionAlgorithm,
+
+    /// Either one of the `CompressionAlgorithm` or the id
+    /// of the custom codec, see the
+    /// `CompressionRegistry`
+    pub algorithm: AlgorithmId,
+}
+
+/// Information about the public client, sent along with the
+/// `Connect` frame if `CONNECT_METADATA` capability was
+/// negotiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectMetadata {
+    pub address: SocketAddr,
+    pub listener_port: u16,
+
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessAction {
+    Allow,
+    Deny,
 }
 
 #[derive(Debug, Clone)]
@@ -41,12 +68,21 @@ pub enum Frame {
     },
     Error(ProtocolError),
 
+    /// Sent by the client, server responds with the
+    /// negotiated subset. Client must not send compressed
+    /// `Forward` frames until the response is received, as
+    /// compression may change with it.
+    Capabilities {
+        capabilities: Capabilities,
+    },
+
     Connect {
         id: u16,
+        metadata: Option<ConnectMetadata>,
     },
     Forward {
         id: u16,
-        buffer: Vec<u8>,
+        buffer: Bytes,
     },
     Disconnect {
         id: u16,
@@ -55,6 +91,46 @@ pub enum Frame {
     AuthThroughMagic {
         magic: String,
     },
+
+    /// Server stopped accepting new connections and will
+    /// close the remaining ones after `drain_timeout`
+    /// seconds
+    ShutdownNotice {
+        drain_timeout: u16,
+    },
+
+    /// Allow or deny public clients from the specified
+    /// network, applies to the servers created after
+    /// this frame
+    AccessRule {
+        action: AccessAction,
+        network: IpNet,
+    },
+
+    /// Keepalive probe, can be sent by both sides if `ECHO`
+    /// capability was negotiated. Receiver must answer with
+    /// the `EchoResponse` carrying the same nonce and
+    /// timestamp, both are opaque to the receiver.
+    ///
+    /// Timestamp lets the sender measure round-trip time
+    /// without keeping track of the sent requests.
+    EchoRequest {
+        nonce: u32,
+        timestamp: u64,
+    },
+    EchoResponse {
+        nonce: u32,
+        timestamp: u64,
+    },
+
+    /// Sent by the client with the id of its zstd
+    /// dictionary, zero disables the dictionary. Server
+    /// echoes the id back if it has the same dictionary or
+    /// responds with the `DictionaryMismatch` error, in
+    /// which case compression stays as it was.
+    Dictionary {
+        id: u32,
+    },
 }
 
 impl_variants! {
@@ -70,5 +146,10 @@ impl_variants! {
         const AUTH_MAGIC    = 6;
 
         const UPDATE_RIGHTS = 7;
+        const CAPABILITIES  = 8;
+        const ACCESS_RULE   = 9;
+        const SHUTDOWN      = 10;
+        const ECHO          = 11;
+        const DICTIONARY    = 12;
     }
 }
@@ -1,7 +1,10 @@
+pub mod codec;
 pub mod frame;
 pub mod reader;
 pub mod writer;
 
+pub mod rtt;
+
 pub mod error;
 
 mod codec_utils;
@@ -1,54 +1,46 @@
 use std::{
-    future::{
-        poll_fn,
-        Future,
-    },
     io,
     num::NonZeroU16,
-    pin::Pin,
 };
 
-use common::protocol::{
-    error::ProtocolError,
-    types::{
-        CodecSide,
-        CompressionAlgorithm,
-        PacketFlags,
-        Protocol,
-        Rights,
-    },
-};
-use neogrok_compression::{
-    error::{
-        DecompressError,
-        DecompressResult,
-    },
-    polymorphic::BufDecompressor,
+use bytes::BytesMut;
+use common::protocol::types::{
+    CodecSide,
+    PacketFlags,
 };
+use neogrok_compression::polymorphic::BufDecompressor;
 use tokio::io::{
     AsyncRead,
     AsyncReadExt,
-    ReadBuf,
 };
 
 use super::{
-    error::ReadError,
-    frame::{
-        Compression,
-        Frame,
+    codec::{
+        decompress_payload,
+        Decoded,
+        HisuiDecoder,
     },
+    error::ReadError,
+    frame::Frame,
 };
+use crate::compression::offload::CompressionPool;
+
+/// Initial capacity of the read buffer, grows to fit the
+/// largest frame
+const READ_BUFFER_CAPACITY: usize = 4096;
 
 pub struct HisuiReader<Reader> {
     inner: Reader,
-    side: CodecSide,
+    buffer: BytesMut,
 
-    pub(crate) decompressor: BufDecompressor,
+    pub(crate) decoder: HisuiDecoder,
+    offload: Option<CompressionPool>,
 }
 
 impl<Reader> HisuiReader<Reader> {
+    /// Buffered bytes which weren't decoded yet are lost
     pub fn into_inner(self) -> (Reader, BufDecompressor) {
-        (self.inner, self.decompressor)
+        (self.inner, self.decoder.decompressor)
     }
 }
 
@@ -71,252 +63,79 @@ where
         flags: PacketFlags,
         max_fwd_buffer: Option<NonZeroU16>,
     ) -> Result<Frame, ReadError> {
-        Ok(match pkt_type {
-            Frame::SERVER if self.side == CodecSide::Server => {
-                return self.read_req_server_frame(flags).await
-            }
-            Frame::SERVER if self.side == CodecSide::Client => {
-                Frame::ServerResponse {
-                    port: self.inner.read_u16_le().await?,
-                }
-            }
-
-            Frame::UPDATE_RIGHTS => {
-                let rights = self.inner.read_u8().await?;
-                Frame::UpdateRights {
-                    new_rights: Rights::from_bits(rights)
-                        .ok_or(ReadError::InvalidRights { rights })?,
-                }
+        self.decoder.set_max_fwd_buffer(max_fwd_buffer);
+        let decoded = loop {
+            if let Some(decoded) = self.decoder.decode_body_raw(
+                pkt_type,
+                flags,
+                &mut self.buffer,
+            )? {
+                break decoded;
             }
 
-            Frame::PING if self.side == CodecSide::Server => {
-                Frame::PingRequest
-            }
-            Frame::PING if self.side == CodecSide::Client => {
-                Frame::PingResponse {
-                    compression: self.read_compression_details().await?,
-                    buffer_size: self.inner.read_u16_le().await?,
-                    server_name: self.read_string_prefixed().await?,
-                }
-            }
-
-            Frame::CONNECT => Frame::Connect {
-                id: self.read_client_id(flags).await?,
-            },
-
-            Frame::FORWARD => {
-                let id = self.read_client_id(flags).await?;
-                let length = self.read_length(flags).await? as usize;
-
-                if let Some(max_length) = max_fwd_buffer {
-                    if length > max_length.get() as usize {
-                        self.skip_n_bytes(length).await?;
-                        return Err(ReadError::TooLongBuffer);
-                    }
-                }
-
-                let buffer = self
-                    .read_fwd_payload(length, flags, max_fwd_buffer)
-                    .await?;
-
-                Frame::Forward { id, buffer }
-            }
-
-            Frame::DISCONNECT => Frame::Disconnect {
-                id: self.read_client_id(flags).await?,
-            },
-
-            Frame::AUTH_MAGIC => Frame::AuthThroughMagic {
-                magic: self.read_string_prefixed().await?,
-            },
-
-            Frame::ERROR => {
-                let code = self.inner.read_u8().await?;
-                Frame::Error(
-                    ProtocolError::try_from(code).map_err(|()| {
-                        ReadError::InvalidErrorCode { code }
-                    })?,
-                )
-            }
+            self.fill().await?;
+        };
 
-            _ => {
-                return Err(ReadError::InvalidPacketType {
-                    pkt_type,
-                    flags,
+        match (decoded, &self.offload) {
+            (Decoded::Compressed { id, buffer }, Some(pool))
+                if pool.should_offload(buffer.len()) =>
+            {
+                let max_size = self.decoder.max_decompressed_size();
+                let buffer = pool
+                    .decompress(&mut self.decoder.decompressor, move |d| {
+                        decompress_payload(d, &buffer, max_size)
+                    })
+                    .await??;
+
+                Ok(Frame::Forward {
+                    id,
+                    buffer: buffer.into(),
                 })
             }
-        })
-    }
-
-    pub async fn read_packet_type(
-        &mut self,
-    ) -> Result<(u8, PacketFlags), ReadError> {
-        let data = self.inner.read_u8().await?;
-        let (pkt_type, flags) = (
-            data >> 3,
-            PacketFlags::from_bits(data & 0b111).ok_or(
-                ReadError::InvalidPacketFlags {
-                    flags: data & 0b111,
-                },
-            )?,
-        );
-
-        Ok((pkt_type, flags))
-    }
-
-    // Helpers
-
-    async fn read_req_server_frame(
-        &mut self,
-        flags: PacketFlags,
-    ) -> Result<Frame, ReadError> {
-        let protocol = if flags.contains(PacketFlags::COMPRESSED) {
-            Protocol::Udp
-        } else if flags.contains(PacketFlags::SHORT2) {
-            Protocol::Tcp
-        } else {
-            return Err(ReadError::InvalidProtocol);
-        };
-        let port = if flags.contains(PacketFlags::SHORT) {
-            0
-        } else {
-            self.inner.read_u16_le().await?
-        };
-
-        Ok(Frame::ServerRequest { port, protocol })
-    }
 
-    async fn read_compression_details(
-        &mut self,
-    ) -> Result<Compression, ReadError> {
-        let algorithm =
-            CompressionAlgorithm::try_from(self.inner.read_u8().await?)
-                .map_err(|()| ReadError::FailedToReadCompressionDetails)?;
-        let level = self.inner.read_u8().await?;
-        Ok(Compression { algorithm, level })
-    }
-
-    async fn skip_n_bytes(&mut self, size: usize) -> io::Result<()> {
-        let mut buf = [0; 64];
-        let mut skipped = 0;
-
-        while skipped < size {
-            let read = self
-                .inner
-                .read(&mut buf[..(size - skipped).min(64)])
-                .await?;
-            skipped += read;
-        }
-
-        Ok(())
-    }
-
-    fn try_decompress(
-        &mut self,
-        input: &[u8],
-        max_size: usize,
-    ) -> DecompressResult<Vec<u8>> {
-        let length = input.len() << 1;
-
-        match self
-            .decompressor
-            .decompress_constrained(input, length)
-        {
-            Ok(buffer) => Ok(buffer),
-            Err(DecompressError::InsufficientSpace) => self
-                .decompressor
-                .decompress_constrained(input, max_size),
-            Err(e) => Err(e),
+            (decoded, _) => self.decoder.finish(decoded),
         }
     }
 
-    async fn read_fwd_payload(
+    /// Cancel safe, read bytes are kept in the buffer
+    pub async fn read_packet_type(
         &mut self,
-        length: usize,
-        flags: PacketFlags,
-        max_size: Option<NonZeroU16>,
-    ) -> Result<Vec<u8>, ReadError> {
-        let mut buffer = self.read_exact(length).await?;
-
-        if flags.contains(PacketFlags::COMPRESSED) {
-            match max_size {
-                Some(s) => {
-                    let max_size = s.get() as usize;
-                    buffer = self.try_decompress(&buffer, max_size)?;
-                }
-
-                None => {
-                    buffer = self
-                        .decompressor
-                        .decompress_unconstrained(&buffer)?;
-                }
+    ) -> Result<(u8, PacketFlags), ReadError> {
+        loop {
+            if let Some(header) =
+                self.decoder.decode_header(&mut self.buffer)?
+            {
+                break Ok(header);
             }
-        }
-
-        Ok(buffer)
-    }
-
-    fn read_length(
-        &mut self,
-        flags: PacketFlags,
-    ) -> impl Future<Output = io::Result<u16>> + '_ {
-        self.read_variadic(flags, PacketFlags::SHORT)
-    }
-
-    fn read_client_id(
-        &mut self,
-        flags: PacketFlags,
-    ) -> impl Future<Output = io::Result<u16>> + '_ {
-        self.read_variadic(flags, PacketFlags::SHORT2)
-    }
 
-    async fn read_variadic(
-        &mut self,
-        flags: PacketFlags,
-        need: PacketFlags,
-    ) -> io::Result<u16> {
-        if flags.contains(need) {
-            let data = self.inner.read_u8().await?;
-            Ok(data as u16)
-        } else {
-            self.inner.read_u16_le().await
+            self.fill().await?;
         }
     }
 
-    async fn read_string_prefixed(&mut self) -> Result<String, ReadError> {
-        let buffer = self.read_buffer_prefixed().await?;
-        String::from_utf8(buffer).map_err(|_| ReadError::InvalidString)
-    }
-
-    async fn read_buffer_prefixed(&mut self) -> io::Result<Vec<u8>> {
-        let length = self.inner.read_u8().await? as usize;
-        self.read_exact(length).await
-    }
-
-    async fn read_exact(
-        &mut self,
-        capacity: usize,
-    ) -> io::Result<Vec<u8>> {
-        let mut buffer: Vec<u8> = Vec::with_capacity(capacity);
-        let mut rd_buffer = ReadBuf::uninit(buffer.spare_capacity_mut());
-
-        while rd_buffer.filled().len() != capacity {
-            poll_fn(|cx| {
-                Pin::new(&mut self.inner).poll_read(cx, &mut rd_buffer)
-            })
-            .await?;
+    /// Stream closed by the peer fails with
+    /// [`io::ErrorKind::UnexpectedEof`] instead of being
+    /// polled again, buffered part of the frame is left
+    /// undecoded
+    async fn fill(&mut self) -> io::Result<()> {
+        if self.buffer.capacity() == self.buffer.len() {
+            self.buffer.reserve(READ_BUFFER_CAPACITY);
         }
 
-        let filled = rd_buffer.filled().len();
-        unsafe { buffer.set_len(filled) }
-
-        Ok(buffer)
+        match self.inner.read_buf(&mut self.buffer).await? {
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => Ok(()),
+        }
     }
 }
 
 impl<Reader> HisuiReader<Reader> {
     // Creation
 
+    /// Decompress large buffers on the `pool`
+    pub fn offload_to(&mut self, pool: CompressionPool) {
+        self.offload = Some(pool);
+    }
+
     pub fn server(reader: Reader, decompressor: BufDecompressor) -> Self {
         Self::new(reader, CodecSide::Server, decompressor)
     }
@@ -332,8 +151,9 @@ impl<Reader> HisuiReader<Reader> {
     ) -> Self {
         Self {
             inner: reader,
-            side,
-            decompressor,
+            buffer: BytesMut::with_capacity(READ_BUFFER_CAPACITY),
+            decoder: HisuiDecoder::new(side, decompressor),
+            offload: None,
         }
     }
 }
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+/// Round-trip time statistics collected from the echo
+/// frames. Smoothed RTT and its variation are calculated
+/// as in TCP (RFC 6298).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RttStats {
+    samples: u64,
+
+    last: Duration,
+    min: Duration,
+    max: Duration,
+
+    smoothed: Duration,
+    variation: Duration,
+}
+
+impl RttStats {
+    pub fn record(&mut self, rtt: Duration) {
+        if self.samples == 0 {
+            self.min = rtt;
+            self.max = rtt;
+            self.smoothed = rtt;
+            self.variation = rtt / 2;
+        } else {
+            self.min = self.min.min(rtt);
+            self.max = self.max.max(rtt);
+
+            // variation = 3/4 * variation + 1/4 * |smoothed - rtt|
+            // smoothed = 7/8 * smoothed + 1/8 * rtt
+            let deviation = self.smoothed.abs_diff(rtt);
+            self.variation = (self.variation * 3 + deviation) / 4;
+            self.smoothed = (self.smoothed * 7 + rtt) / 8;
+        }
+
+        self.last = rtt;
+        self.samples += 1;
+    }
+
+    pub const fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// `None` if nothing was recorded yet
+    pub fn last(&self) -> Option<Duration> {
+        self.recorded(self.last)
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.recorded(self.min)
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.recorded(self.max)
+    }
+
+    pub fn smoothed(&self) -> Option<Duration> {
+        self.recorded(self.smoothed)
+    }
+
+    pub fn variation(&self) -> Option<Duration> {
+        self.recorded(self.variation)
+    }
+
+    fn recorded(&self, value: Duration) -> Option<Duration> {
+        (self.samples != 0).then_some(value)
+    }
+}
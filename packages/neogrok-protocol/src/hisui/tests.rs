@@ -1,16 +1,442 @@
-use common::protocol::types::*;
+use std::{
+    io::{
+        self,
+        IoSlice,
+    },
+    net::SocketAddr,
+    num::{
+        NonZeroU16,
+        NonZeroUsize,
+    },
+    pin::Pin,
+    task::{
+        Context,
+        Poll,
+    },
+    time::Duration,
+};
+
+use bytes::{
+    Bytes,
+    BytesMut,
+};
+use common::protocol::{
+    error::ProtocolError,
+    types::*,
+};
+use neogrok_compression::{
+    error::DecompressError,
+    polymorphic::{
+        BufCompressor,
+        BufDecompressor,
+    },
+    registry,
+};
+use tokio::io::{
+    AsyncRead,
+    AsyncWrite,
+    ReadBuf,
+};
+use tokio_util::codec::{
+    Decoder,
+    Encoder,
+};
 
 use super::codec_utils::encode_request_server_header;
-use crate::hisui::{
-    codec_utils::{
-        encode_client_header,
-        encode_fwd_header,
-        encode_type,
-        just_type,
+use crate::{
+    compression::{
+        adaptive::AdaptivePolicy,
+        offload::CompressionPool,
+        types::CompressionStrategy,
+    },
+    hisui::{
+        codec::{
+            HisuiDecoder,
+            HisuiEncoder,
+        },
+        codec_utils::{
+            encode_client_header,
+            encode_fwd_header,
+            encode_type,
+            just_type,
+        },
+        error::ReadError,
+        frame::{
+            AccessAction,
+            Compression,
+            ConnectMetadata,
+            Frame,
+        },
+        reader::HisuiReader,
+        rtt::RttStats,
+        writer::{
+            FlushPolicy,
+            HisuiWriter,
+        },
     },
-    frame::Frame,
 };
 
+/// Encoded frame of every type together with the side
+/// which decodes it
+fn encode_every_frame() -> Vec<(CodecSide, BytesMut)> {
+    let metadata = ConnectMetadata {
+        address: "[::1]:80".parse().unwrap(),
+        listener_port: 8080,
+        timestamp: 1_671_000_000_000,
+    };
+    let frames = [
+        (
+            CodecSide::Server,
+            Frame::ServerRequest {
+                port: 8080,
+                protocol: Protocol::Tcp,
+            },
+        ),
+        (CodecSide::Client, Frame::ServerResponse { port: 8080 }),
+        (CodecSide::Server, Frame::PingRequest),
+        (
+            CodecSide::Client,
+            Frame::PingResponse {
+                server_name: "neogrok".to_owned(),
+                buffer_size: 1024,
+                compression: Compression {
+                    level: 10,
+                    algorithm: registry::ZSTD,
+                },
+            },
+        ),
+        (
+            CodecSide::Client,
+            Frame::UpdateRights {
+                new_rights: Rights::all(),
+            },
+        ),
+        (CodecSide::Client, Frame::Error(ProtocolError::AccessDenied)),
+        (
+            CodecSide::Server,
+            Frame::Capabilities {
+                capabilities: Capabilities::all(),
+            },
+        ),
+        (
+            CodecSide::Client,
+            Frame::Connect {
+                id: 1,
+                metadata: None,
+            },
+        ),
+        (
+            CodecSide::Client,
+            Frame::Connect {
+                id: 1,
+                metadata: Some(metadata),
+            },
+        ),
+        (
+            CodecSide::Server,
+            Frame::Forward {
+                id: 1,
+                buffer: Bytes::from_static(b"short"),
+            },
+        ),
+        (
+            CodecSide::Server,
+            Frame::Forward {
+                id: 1000,
+                buffer: Bytes::from(vec![1; 300]),
+            },
+        ),
+        (CodecSide::Server, Frame::Disconnect { id: 1 }),
+        (
+            CodecSide::Server,
+            Frame::AuthThroughMagic {
+                magic: "insecure".to_owned(),
+            },
+        ),
+        (
+            CodecSide::Client,
+            Frame::ShutdownNotice { drain_timeout: 30 },
+        ),
+        (
+            CodecSide::Client,
+            Frame::AccessRule {
+                action: AccessAction::Deny,
+                network: "fd00::/8".parse().unwrap(),
+            },
+        ),
+        (
+            CodecSide::Server,
+            Frame::EchoRequest {
+                nonce: 1,
+                timestamp: 2,
+            },
+        ),
+        (
+            CodecSide::Server,
+            Frame::EchoResponse {
+                nonce: 1,
+                timestamp: 2,
+            },
+        ),
+        (CodecSide::Server, Frame::Dictionary { id: 7 }),
+    ];
+
+    let mut encoder = HisuiEncoder::new(BufCompressor::deflate(6));
+    let mut encoded: Vec<_> = frames
+        .into_iter()
+        .map(|(side, frame)| {
+            let mut dst = BytesMut::new();
+            encoder.encode(frame, &mut dst).unwrap();
+            (side, dst)
+        })
+        .collect();
+
+    let mut compressed = BytesMut::new();
+    encoder
+        .encode_forward(
+            1,
+            &[b'a'; 300],
+            CompressionStrategy::TryCompress { with_threshold: 0 },
+            &mut compressed,
+        )
+        .unwrap();
+    encoded.push((CodecSide::Server, compressed));
+
+    encoded
+}
+
+fn is_unexpected_eof<T>(result: Result<T, ReadError>) -> bool {
+    matches!(
+        result,
+        Err(ReadError::Io(error)) if error.kind() == io::ErrorKind::UnexpectedEof
+    )
+}
+
+/// Stream closed by the peer, fails the test if it's read
+/// again after reporting its end
+struct Truncated<'a> {
+    data: &'a [u8],
+    ended: bool,
+}
+
+impl<'a> Truncated<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self { data, ended: false }
+    }
+}
+
+impl AsyncRead for Truncated<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.data.is_empty() {
+            assert!(!this.ended, "closed stream is read again");
+            this.ended = true;
+        }
+
+        let length = this.data.len().min(buf.remaining());
+        buf.put_slice(&this.data[..length]);
+        this.data = &this.data[length..];
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Records every write issued to it separately, accepts at
+/// most `max_write` bytes at once if set
+#[derive(Default)]
+struct WriteLog {
+    writes: Vec<Vec<u8>>,
+    max_write: Option<usize>,
+}
+
+impl WriteLog {
+    fn written(&self) -> Vec<u8> {
+        self.writes.concat()
+    }
+}
+
+impl AsyncWrite for WriteLog {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut write: Vec<u8> = bufs
+            .iter()
+            .flat_map(|buf| buf.iter().copied())
+            .collect();
+        write.truncate(this.max_write.unwrap_or(usize::MAX));
+
+        let length = write.len();
+        this.writes.push(write);
+        Poll::Ready(Ok(length))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn test_connect_metadata_roundtrip() {
+    let addresses: [SocketAddr; 2] = [
+        "127.0.0.1:4567".parse().unwrap(),
+        "[::1]:80".parse().unwrap(),
+    ];
+
+    for address in addresses {
+        let metadata = ConnectMetadata {
+            address,
+            listener_port: 8080,
+            timestamp: 1_671_000_000_000,
+        };
+        let mut writer =
+            HisuiWriter::new(Vec::new(), BufCompressor::deflate(1));
+        writer
+            .write_connect_with_metadata(1024, &metadata)
+            .await
+            .unwrap();
+
+        let (buffer, _) = writer.into_inner();
+        let mut reader = HisuiReader::client(
+            buffer.as_slice(),
+            BufDecompressor::deflate(),
+        );
+        match reader
+            .read_frame_inconcurrent(None)
+            .await
+            .unwrap()
+        {
+            Frame::Connect {
+                id,
+                metadata: Some(read),
+            } => {
+                assert_eq!(id, 1024);
+                assert_eq!(read, metadata);
+            }
+
+            frame => panic!("unexpected frame: {frame:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_access_rule_roundtrip() {
+    let rules = [
+        (AccessAction::Allow, "10.0.0.0/8".parse().unwrap()),
+        (AccessAction::Deny, "fd00::/8".parse().unwrap()),
+    ];
+
+    for (action, network) in rules {
+        let mut writer =
+            HisuiWriter::new(Vec::new(), BufCompressor::deflate(1));
+        writer
+            .write_access_rule(action, &network)
+            .await
+            .unwrap();
+
+        let (buffer, _) = writer.into_inner();
+        let mut reader = HisuiReader::server(
+            buffer.as_slice(),
+            BufDecompressor::deflate(),
+        );
+        match reader
+            .read_frame_inconcurrent(None)
+            .await
+            .unwrap()
+        {
+            Frame::AccessRule {
+                action: read_action,
+                network: read_network,
+            } => {
+                assert_eq!(read_action, action);
+                assert_eq!(read_network, network);
+            }
+
+            frame => panic!("unexpected frame: {frame:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_echo_roundtrip() {
+    let mut writer =
+        HisuiWriter::new(Vec::new(), BufCompressor::deflate(1));
+    writer
+        .request_echo(0xdead_beef, u64::MAX)
+        .await
+        .unwrap();
+    writer.respond_echo(7, 1).await.unwrap();
+
+    let (buffer, _) = writer.into_inner();
+    let mut reader =
+        HisuiReader::server(buffer.as_slice(), BufDecompressor::deflate());
+    assert!(matches!(
+        reader
+            .read_frame_inconcurrent(None)
+            .await
+            .unwrap(),
+        Frame::EchoRequest {
+            nonce: 0xdead_beef,
+            timestamp: u64::MAX
+        }
+    ));
+    assert!(matches!(
+        reader
+            .read_frame_inconcurrent(None)
+            .await
+            .unwrap(),
+        Frame::EchoResponse {
+            nonce: 7,
+            timestamp: 1
+        }
+    ));
+}
+
+#[test]
+fn test_rtt_stats() {
+    let mut stats = RttStats::default();
+    assert_eq!(stats.smoothed(), None);
+
+    stats.record(Duration::from_millis(100));
+    assert_eq!(stats.smoothed(), Some(Duration::from_millis(100)));
+    assert_eq!(stats.variation(), Some(Duration::from_millis(50)));
+
+    stats.record(Duration::from_millis(20));
+    assert_eq!(stats.samples(), 2);
+    assert_eq!(stats.last(), Some(Duration::from_millis(20)));
+    assert_eq!(stats.min(), Some(Duration::from_millis(20)));
+    assert_eq!(stats.max(), Some(Duration::from_millis(100)));
+    assert_eq!(stats.smoothed(), Some(Duration::from_millis(90)));
+    assert_eq!(stats.variation(), Some(Duration::from_micros(57_500)));
+}
+
 #[test]
 fn test_req_server_encoder() {
     assert_eq!(
@@ -129,3 +555,446 @@ fn test_fwd_flags_application() {
         )
     )
 }
+
+#[tokio::test]
+async fn test_stream_compression_roundtrip() {
+    let payload = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    let strategy = CompressionStrategy::TryCompress { with_threshold: 0 };
+
+    let mut writer =
+        HisuiWriter::new(Vec::new(), BufCompressor::zstd_stream(3));
+    let mut sizes = Vec::new();
+    for id in 0..3 {
+        let status = writer
+            .write_forward(id, Bytes::from_static(payload), strategy)
+            .await
+            .unwrap()
+            .unwrap();
+        sizes.push(status.after);
+    }
+    assert!(sizes[1] < sizes[0]);
+
+    let (buffer, _) = writer.into_inner();
+    let mut reader = HisuiReader::server(
+        buffer.as_slice(),
+        BufDecompressor::zstd_stream(),
+    );
+    for expected_id in 0..3 {
+        match reader
+            .read_frame_inconcurrent(NonZeroU16::new(payload.len() as u16))
+            .await
+            .unwrap()
+        {
+            Frame::Forward { id, buffer } => {
+                assert_eq!(id, expected_id);
+                assert_eq!(buffer, &payload[..]);
+            }
+
+            frame => panic!("unexpected frame: {frame:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_dictionary_roundtrip() {
+    let mut writer = HisuiWriter::new(Vec::new(), BufCompressor::zstd(3));
+    writer
+        .request_dictionary(0xdead_beef)
+        .await
+        .unwrap();
+    writer.respond_dictionary(0).await.unwrap();
+
+    let (buffer, _) = writer.into_inner();
+    let mut reader =
+        HisuiReader::server(buffer.as_slice(), BufDecompressor::zstd());
+    for expected in [0xdead_beef, 0] {
+        assert!(matches!(
+            reader.read_frame_inconcurrent(None).await.unwrap(),
+            Frame::Dictionary { id } if id == expected
+        ));
+    }
+
+    assert!(matches!(
+        ReadError::from(DecompressError::DictionaryMismatch),
+        ReadError::DictionaryMismatch
+    ));
+}
+
+#[test]
+fn test_builtin_algorithm_ids() {
+    assert_eq!(CompressionAlgorithm::Deflate as u8, registry::DEFLATE);
+    assert_eq!(CompressionAlgorithm::ZStd as u8, registry::ZSTD);
+    assert_eq!(CompressionAlgorithm::Lz4 as u8, registry::LZ4);
+    assert_eq!(CompressionAlgorithm::Brotli as u8, registry::BROTLI);
+}
+
+#[tokio::test]
+async fn test_adaptive_compression_backoff() {
+    let strategy = CompressionStrategy::Adaptive {
+        with_threshold: 0,
+        policy: AdaptivePolicy {
+            min_ratio: 1.1,
+            skip_frames: 2,
+            max_skip_frames: 3,
+        },
+    };
+    // xorshift output barely compresses
+    let mut state = 0x2545_f491_u32;
+    let noise: Bytes = (0..512)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+
+    let mut writer =
+        HisuiWriter::new(Vec::new(), BufCompressor::deflate(6));
+    for _ in 0..8 {
+        writer
+            .write_forward(1, noise.clone(), strategy)
+            .await
+            .unwrap();
+    }
+
+    // attempt, skip 2, probe, skip 3, probe, skip 1 of 3
+    let stats = writer.compression_stats();
+    assert_eq!((stats.attempted, stats.skipped), (3, 5));
+
+    // Compressible traffic of another client is unaffected
+    let status = writer
+        .write_forward(2, Bytes::from_static(&[b'a'; 512]), strategy)
+        .await
+        .unwrap();
+    assert!(status.is_some());
+
+    // Reconnected client starts over
+    writer.write_connect(1).await.unwrap();
+    writer
+        .write_forward(1, noise, strategy)
+        .await
+        .unwrap();
+    let stats = writer.compression_stats();
+    assert_eq!((stats.attempted, stats.skipped), (5, 5));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_offloaded_compression_order() {
+    let pool = CompressionPool::new(NonZeroUsize::new(2).unwrap(), 64);
+    let strategy = CompressionStrategy::TryCompress { with_threshold: 0 };
+    let payloads: Vec<Bytes> = (0..16_u8)
+        .map(|i| {
+            // Short payloads are compressed in place
+            let length = if i % 2 == 0 { 32 } else { 1024 };
+            Bytes::from(vec![b'a' + i; length])
+        })
+        .collect();
+
+    let mut writer =
+        HisuiWriter::new(Vec::new(), BufCompressor::zstd_stream(19));
+    writer.offload_to(pool);
+    for (id, payload) in payloads.iter().enumerate() {
+        writer
+            .write_forward(id as u16, payload.clone(), strategy)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    let (buffer, _) = writer.into_inner();
+    let mut reader = HisuiReader::server(
+        buffer.as_slice(),
+        BufDecompressor::zstd_stream(),
+    );
+    // Compressed frames are short, offload all of them
+    reader.offload_to(CompressionPool::new(NonZeroUsize::MIN, 0));
+    for (expected_id, payload) in payloads.iter().enumerate() {
+        match reader
+            .read_frame_inconcurrent(NonZeroU16::new(1024))
+            .await
+            .unwrap()
+        {
+            Frame::Forward { id, buffer } => {
+                assert_eq!(id as usize, expected_id);
+                assert_eq!(&buffer, payload);
+            }
+
+            frame => panic!("unexpected frame: {frame:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_codec_partial_input() {
+    let frames = [
+        Frame::PingRequest,
+        Frame::Connect {
+            id: 300,
+            metadata: Some(ConnectMetadata {
+                address: "[::1]:4567".parse().unwrap(),
+                listener_port: 8080,
+                timestamp: 1_671_000_000_000,
+            }),
+        },
+        Frame::Forward {
+            id: 300,
+            buffer: b"hello ".repeat(64).into(),
+        },
+        Frame::EchoResponse {
+            nonce: 7,
+            timestamp: 1_671_000_000_000,
+        },
+        Frame::AccessRule {
+            action: AccessAction::Deny,
+            network: "10.0.0.0/8".parse().unwrap(),
+        },
+        Frame::Disconnect { id: 300 },
+    ];
+
+    let mut encoder = HisuiEncoder::new(BufCompressor::deflate(6));
+    encoder.set_strategy(CompressionStrategy::TryCompress {
+        with_threshold: 0,
+    });
+    let mut encoded = BytesMut::new();
+    for frame in frames.iter().cloned() {
+        encoder.encode(frame, &mut encoded).unwrap();
+    }
+    assert_eq!(encoder.compression_stats().attempted, 1);
+
+    // Frames are decoded only when the last byte arrives
+    let mut decoder = HisuiDecoder::server(BufDecompressor::deflate());
+    let mut src = BytesMut::new();
+    let mut decoded = Vec::new();
+    for &byte in encoded.iter() {
+        src.extend_from_slice(&[byte]);
+        if let Some(frame) = decoder.decode(&mut src).unwrap() {
+            assert!(src.is_empty());
+            decoded.push(frame);
+        }
+    }
+
+    assert_eq!(format!("{decoded:?}"), format!("{frames:?}"));
+}
+
+#[test]
+fn test_codec_skips_too_long_forward() {
+    let mut encoder = HisuiEncoder::new(BufCompressor::deflate(6));
+    let mut encoded = BytesMut::new();
+    encoder
+        .encode(
+            Frame::Forward {
+                id: 1,
+                buffer: Bytes::from(vec![0; 600]),
+            },
+            &mut encoded,
+        )
+        .unwrap();
+    encoder
+        .encode(Frame::Disconnect { id: 1 }, &mut encoded)
+        .unwrap();
+
+    let mut decoder = HisuiDecoder::server(BufDecompressor::deflate());
+    decoder.set_max_fwd_buffer(NonZeroU16::new(512));
+    let mut src = encoded.split_to(100);
+    assert!(matches!(
+        decoder.decode(&mut src),
+        Err(ReadError::TooLongBuffer)
+    ));
+
+    // Rest of the payload is discarded as it arrives
+    assert!(decoder.decode(&mut src).unwrap().is_none());
+    src.extend_from_slice(&encoded);
+    assert!(matches!(
+        decoder.decode(&mut src).unwrap(),
+        Some(Frame::Disconnect { id: 1 })
+    ));
+    assert!(src.is_empty());
+}
+
+#[tokio::test]
+async fn test_coalesced_writes() {
+    let mut writer =
+        HisuiWriter::new(WriteLog::default(), BufCompressor::deflate(1));
+    writer.coalesce(FlushPolicy {
+        max_bytes: 64,
+        max_frames: 4,
+    });
+
+    // Frame count limit
+    for id in 0..4 {
+        writer.write_connect(id).await.unwrap();
+    }
+    // Size limit
+    writer
+        .write_forward(
+            4,
+            Bytes::from(vec![0; 80]),
+            CompressionStrategy::Disable,
+        )
+        .await
+        .unwrap();
+    // Rest is written by the explicit flush only
+    writer.write_disconnect(0).await.unwrap();
+    writer.request_ping().await.unwrap();
+    writer.flush().await.unwrap();
+    writer.flush().await.unwrap();
+
+    let (log, _) = writer.into_inner();
+    assert_eq!(log.writes.len(), 3);
+
+    let mut decoder = HisuiDecoder::server(BufDecompressor::deflate());
+    let mut frames = Vec::new();
+    for write in log.writes {
+        let mut src = BytesMut::from(write.as_slice());
+        while let Some(frame) = decoder.decode(&mut src).unwrap() {
+            frames.push(frame);
+        }
+        // Frames are never split between the writes
+        assert!(src.is_empty());
+    }
+
+    assert_eq!(frames.len(), 7);
+    assert!(matches!(frames[3], Frame::Connect { id: 3, .. }));
+    assert!(matches!(
+        &frames[4],
+        Frame::Forward { id: 4, buffer } if buffer.len() == 80
+    ));
+    assert!(matches!(frames[6], Frame::PingRequest));
+}
+
+#[tokio::test]
+async fn test_truncated_frames() {
+    let max_fwd_buffer = NonZeroU16::new(1024);
+    for (side, encoded) in encode_every_frame() {
+        let mut reader = HisuiReader::new(
+            &encoded[..],
+            side,
+            BufDecompressor::deflate(),
+        );
+        reader
+            .read_frame_inconcurrent(max_fwd_buffer)
+            .await
+            .unwrap();
+
+        for length in 0..encoded.len() {
+            let truncated = &encoded[..length];
+            let mut reader = HisuiReader::new(
+                Truncated::new(truncated),
+                side,
+                BufDecompressor::deflate(),
+            );
+            let read = reader
+                .read_frame_inconcurrent(max_fwd_buffer)
+                .await;
+            assert!(is_unexpected_eof(read), "{length}/{encoded:?}");
+
+            let mut decoder =
+                HisuiDecoder::new(side, BufDecompressor::deflate());
+            let mut src = BytesMut::from(truncated);
+            match length {
+                0 => assert!(decoder
+                    .decode_eof(&mut src)
+                    .unwrap()
+                    .is_none()),
+                _ => assert!(
+                    is_unexpected_eof(decoder.decode_eof(&mut src)),
+                    "{length}/{encoded:?}"
+                ),
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_truncated_skipped_forward() {
+    let mut encoded = BytesMut::new();
+    HisuiEncoder::new(BufCompressor::deflate(6))
+        .encode(
+            Frame::Forward {
+                id: 1,
+                buffer: Bytes::from(vec![0; 600]),
+            },
+            &mut encoded,
+        )
+        .unwrap();
+    let truncated = &encoded[..encoded.len() - 1];
+
+    let mut reader = HisuiReader::server(
+        Truncated::new(truncated),
+        BufDecompressor::deflate(),
+    );
+    assert!(matches!(
+        reader
+            .read_frame_inconcurrent(NonZeroU16::new(512))
+            .await,
+        Err(ReadError::TooLongBuffer)
+    ));
+    assert!(is_unexpected_eof(
+        reader.read_frame_inconcurrent(None).await
+    ));
+
+    let mut decoder = HisuiDecoder::server(BufDecompressor::deflate());
+    decoder.set_max_fwd_buffer(NonZeroU16::new(512));
+    let mut src = BytesMut::from(truncated);
+    assert!(matches!(
+        decoder.decode(&mut src),
+        Err(ReadError::TooLongBuffer)
+    ));
+    assert!(is_unexpected_eof(decoder.decode_eof(&mut src)));
+}
+
+#[tokio::test]
+async fn test_partial_vectored_writes() {
+    let payloads: Vec<Bytes> = (1..=8_u8)
+        .map(|i| Bytes::from(vec![i; i as usize * 10]))
+        .collect();
+    let mut writer = HisuiWriter::new(
+        WriteLog {
+            writes: Vec::new(),
+            max_write: Some(7),
+        },
+        BufCompressor::deflate(1),
+    );
+    writer.coalesce(FlushPolicy {
+        max_bytes: usize::MAX,
+        max_frames: usize::MAX,
+    });
+    for (id, payload) in payloads.iter().enumerate() {
+        writer.write_connect(id as u16).await.unwrap();
+        writer
+            .write_forward(
+                id as u16,
+                payload.clone(),
+                CompressionStrategy::Disable,
+            )
+            .await
+            .unwrap();
+    }
+    writer.flush().await.unwrap();
+
+    let (log, _) = writer.into_inner();
+    let written = log.written();
+    let mut reader = HisuiReader::client(
+        written.as_slice(),
+        BufDecompressor::deflate(),
+    );
+    for (expected_id, payload) in payloads.iter().enumerate() {
+        assert!(matches!(
+            reader.read_frame_inconcurrent(None).await.unwrap(),
+            Frame::Connect { id, .. } if id as usize == expected_id
+        ));
+        match reader
+            .read_frame_inconcurrent(None)
+            .await
+            .unwrap()
+        {
+            Frame::Forward { id, buffer } => {
+                assert_eq!(id as usize, expected_id);
+                assert_eq!(&buffer, payload);
+            }
+
+            frame => panic!("unexpected frame: {frame:?}"),
+        }
+    }
+}
@@ -15,6 +15,6 @@ pub fn replace_compression<Reader, Writer>(
     compressor: BufCompressor,
     decompressor: BufDecompressor,
 ) {
-    reader.decompressor = decompressor;
-    writer.compressor = compressor;
+    reader.decoder.decompressor = decompressor;
+    writer.encoder.compressor = compressor;
 }
@@ -1,4 +1,5 @@
 use std::{
+    collections::VecDeque,
     future::Future,
     io::{
         self,
@@ -6,30 +7,69 @@ use std::{
     },
 };
 
+use bytes::{
+    Buf,
+    Bytes,
+    BytesMut,
+};
 use common::protocol::{
     error::ProtocolError,
     types::*,
 };
-use neogrok_compression::polymorphic::BufCompressor;
+use ipnet::IpNet;
+use neogrok_compression::{
+    polymorphic::BufCompressor,
+    registry::AlgorithmId,
+};
 use tokio::io::AsyncWriteExt;
+use tokio_util::codec::Encoder;
 
 use super::{
-    codec_utils::{
-        encode_client_header,
-        encode_fwd_header,
-        encode_request_server_header,
-        just_type,
+    codec::HisuiEncoder,
+    frame::{
+        AccessAction,
+        Compression,
+        ConnectMetadata,
+        Frame,
     },
-    frame::Frame,
 };
-use crate::compression::types::{
-    CompressionStatus,
-    CompressionStrategy,
+use crate::compression::{
+    adaptive::AdaptiveStats,
+    offload::CompressionPool,
+    types::{
+        CompressionStatus,
+        CompressionStrategy,
+    },
 };
 
+/// Limits of the frames coalesced by the buffered writer,
+/// buffer is written once any of them is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+    pub max_bytes: usize,
+    pub max_frames: usize,
+}
+
+/// Slices passed to the single vectored write
+const MAX_IO_SLICES: usize = 64;
+
 pub struct HisuiWriter<Writer> {
     inner: Writer,
-    pub(crate) compressor: BufCompressor,
+
+    /// Encoded control frames and headers, small enough to
+    /// be copied
+    buffer: BytesMut,
+
+    /// Chunks of the `buffer` interleaved with the shared
+    /// `Forward` payloads, written by one vectored write
+    queue: VecDeque<Bytes>,
+    queued: usize,
+
+    pub(crate) encoder: HisuiEncoder,
+    offload: Option<CompressionPool>,
+
+    coalesce: Option<FlushPolicy>,
+    pending_frames: usize,
 }
 
 impl<Writer> HisuiWriter<Writer>
@@ -38,193 +78,334 @@ where
 {
     // Responders
 
-    pub async fn respond_update_rights(
+    pub fn respond_update_rights(
         &mut self,
         rights: Rights,
-    ) -> io::Result<()> {
-        self.inner
-            .write_all(&[just_type(Frame::UPDATE_RIGHTS), rights.bits()])
-            .await
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::UpdateRights { new_rights: rights })
+    }
+
+    pub fn respond_capabilities(
+        &mut self,
+        capabilities: Capabilities,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::Capabilities { capabilities })
+    }
+
+    pub fn respond_dictionary(
+        &mut self,
+        id: u32,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::Dictionary { id })
     }
 
-    pub async fn respond_server(&mut self, port: u16) -> io::Result<()> {
-        self.inner
-            .write_all(&[
-                just_type(Frame::SERVER),
-                (port & 0xff) as u8,
-                (port >> 8) as u8,
-            ])
-            .await
+    pub fn respond_server(
+        &mut self,
+        port: u16,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::ServerResponse { port })
     }
 
-    pub async fn respond_error(
+    pub fn respond_error(
         &mut self,
         error: ProtocolError,
-    ) -> io::Result<()> {
-        self.inner
-            .write_all(&[just_type(Frame::ERROR), error as _])
-            .await
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::Error(error))
     }
 
-    pub async fn respond_ping(
+    /// Echoes back request's `nonce` and `timestamp`
+    pub fn respond_echo(
+        &mut self,
+        nonce: u32,
+        timestamp: u64,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::EchoResponse { nonce, timestamp })
+    }
+
+    pub fn respond_ping(
         &mut self,
         server_name: &str,
-        algorithm: CompressionAlgorithm,
+        algorithm: AlgorithmId,
         compression_level: u8,
         buffer_size: u16,
-    ) -> io::Result<()> {
-        self.write_vectored(
-            &[
-                just_type(Frame::PING),
-                algorithm as _,
-                compression_level,
-                (buffer_size & 0xff) as u8,
-                (buffer_size >> 8) as u8,
-                server_name.len() as u8,
-            ],
-            server_name.as_bytes(),
-        )
-        .await
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::PingResponse {
+            server_name: server_name.to_owned(),
+            buffer_size,
+            compression: Compression {
+                level: compression_level,
+                algorithm,
+            },
+        })
     }
 
     // Requestors
-    pub async fn request_server(
+    pub fn request_server(
         &mut self,
         port: u16,
         protocol: Protocol,
-    ) -> io::Result<()> {
-        let (hdr, len) = encode_request_server_header(port, protocol);
-        self.inner.write_all(&hdr[..len]).await
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::ServerRequest { port, protocol })
+    }
+
+    pub fn request_capabilities(
+        &mut self,
+        capabilities: Capabilities,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.respond_capabilities(capabilities)
+    }
+
+    /// Zero `id` asks to stop using the dictionary
+    pub fn request_dictionary(
+        &mut self,
+        id: u32,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.respond_dictionary(id)
     }
 
     pub fn request_ping(
         &mut self,
     ) -> impl Future<Output = io::Result<()>> + '_ {
-        self.inner.write_u8(just_type(Frame::PING))
+        self.write_frame(Frame::PingRequest)
+    }
+
+    pub fn request_echo(
+        &mut self,
+        nonce: u32,
+        timestamp: u64,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::EchoRequest { nonce, timestamp })
     }
 
     // Writers
 
-    pub async fn write_auth_through_magic(
+    pub fn write_shutdown_notice(
+        &mut self,
+        drain_timeout: u16,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::ShutdownNotice { drain_timeout })
+    }
+
+    pub fn write_auth_through_magic(
         &mut self,
         magic: &str,
-    ) -> io::Result<()> {
-        self.write_vectored(
-            &[just_type(Frame::AUTH_MAGIC), magic.len() as _],
-            magic.as_bytes(),
-        )
-        .await
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::AuthThroughMagic {
+            magic: magic.to_owned(),
+        })
+    }
+
+    pub fn write_access_rule(
+        &mut self,
+        action: AccessAction,
+        network: &IpNet,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::AccessRule {
+            action,
+            network: *network,
+        })
     }
 
     pub fn write_disconnect(
         &mut self,
         id: u16,
     ) -> impl Future<Output = io::Result<()>> + '_ {
-        self.write_client_related_pkt(Frame::DISCONNECT, id)
+        self.write_frame(Frame::Disconnect { id })
     }
 
     pub async fn write_forward(
         &mut self,
         id: u16,
-        buffer: &[u8],
+        buffer: Bytes,
         strategy: CompressionStrategy,
     ) -> io::Result<Option<CompressionStatus>> {
-        let orig_len = buffer.len();
-        let mut compressed: Vec<u8> = Vec::new();
-        let mut buffer = buffer;
-
-        if let CompressionStrategy::TryCompress { with_threshold } =
-            strategy
-        {
-            if (orig_len as u16) >= with_threshold {
-                if let Some(succ) =
-                    self.compressor.compress(buffer, orig_len)
-                {
-                    compressed = succ;
-                    buffer = &compressed;
-                }
+        let plan = self
+            .encoder
+            .plan_forward(id, buffer.len(), strategy);
+        let compressed = match (plan.max_size, &self.offload) {
+            (Some(max_size), Some(pool))
+                if pool.should_offload(buffer.len()) =>
+            {
+                let shared = Bytes::clone(&buffer);
+                pool.compress(&mut self.encoder.compressor, move |c| {
+                    c.compress(&shared, max_size)
+                })
+                .await?
             }
-        }
 
-        let (hdr, hdr_length) = encode_fwd_header(
+            (Some(max_size), _) => self
+                .encoder
+                .compressor
+                .compress(&buffer, max_size),
+            (None, _) => None,
+        };
+
+        let status = self.encoder.encode_forward_header(
             id,
-            buffer.len() as _,
-            compressed.capacity() != 0,
+            buffer.len(),
+            compressed.as_deref(),
+            plan,
+            &mut self.buffer,
         );
+        self.push_payload(compressed.map_or(buffer, Bytes::from));
 
-        self.write_vectored(&hdr[..hdr_length], buffer)
-            .await
-            .map(|()| {
-                if compressed.capacity() != 0 {
-                    Some(CompressionStatus {
-                        before: orig_len as _,
-                        after: buffer.len() as _,
-                    })
-                } else {
-                    None
-                }
-            })
+        self.frame_written().await.map(|()| status)
     }
 
     pub fn write_connect(
         &mut self,
         id: u16,
     ) -> impl Future<Output = io::Result<()>> + '_ {
-        self.write_client_related_pkt(Frame::CONNECT, id)
+        self.write_frame(Frame::Connect { id, metadata: None })
     }
 
-    // Helpers
-
-    async fn write_client_related_pkt(
+    /// Writes `Connect` frame with the public client
+    /// metadata attached, should be used only if
+    /// `CONNECT_METADATA` capability was negotiated.
+    pub fn write_connect_with_metadata(
         &mut self,
-        pkt_type: u8,
         id: u16,
-    ) -> io::Result<()> {
-        let (hdr, len) = encode_client_header(pkt_type, id);
-        self.inner.write_all(&hdr[..len]).await
+        metadata: &ConnectMetadata,
+    ) -> impl Future<Output = io::Result<()>> + '_ {
+        self.write_frame(Frame::Connect {
+            id,
+            metadata: Some(metadata.clone()),
+        })
     }
 
-    async fn write_vectored(
-        &mut self,
-        prepend: &[u8],
-        append: &[u8],
-    ) -> io::Result<()> {
-        let mut written: usize = 0;
-
-        let mut prepend_io = IoSlice::new(prepend);
-        let mut append_io = IoSlice::new(append);
-
-        let prepend_len = prepend.len();
-        let append_len = append.len();
-        let total = prepend_len + append_len;
+    /// Writes all coalesced frames, no-op for the
+    /// unbuffered writer
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.pending_frames = 0;
+        if self.buffered() == 0 {
+            return Ok(());
+        }
 
-        loop {
-            if written == total {
-                break Ok(());
+        self.split_buffer();
+        while !self.queue.is_empty() {
+            let mut slices = [IoSlice::new(&[]); MAX_IO_SLICES];
+            let mut count = 0;
+            for (slice, chunk) in slices.iter_mut().zip(&self.queue) {
+                *slice = IoSlice::new(chunk);
+                count += 1;
             }
 
-            let amount = self
+            let written = self
                 .inner
-                .write_vectored(&[prepend_io, append_io])
+                .write_vectored(&slices[..count])
                 .await?;
-            written += amount;
-
-            if written < prepend_len {
-                prepend_io = IoSlice::new(&prepend[amount..]);
-            } else {
-                prepend_io = IoSlice::new(&[]);
-                append_io =
-                    IoSlice::new(&append[(written - prepend_len)..]);
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.advance_queue(written);
+        }
+
+        self.inner.flush().await
+    }
+
+    // Helpers
+
+    async fn write_frame(&mut self, frame: Frame) -> io::Result<()> {
+        self.encoder.encode(frame, &mut self.buffer)?;
+        self.frame_written().await
+    }
+
+    async fn frame_written(&mut self) -> io::Result<()> {
+        if let Some(policy) = self.coalesce {
+            self.pending_frames += 1;
+            if self.buffered() < policy.max_bytes
+                && self.pending_frames < policy.max_frames
+            {
+                return Ok(());
             }
         }
+
+        self.flush().await
     }
 }
 
 impl<Writer> HisuiWriter<Writer> {
+    /// Queues `payload` after the encoded header without
+    /// copying it
+    fn push_payload(&mut self, payload: Bytes) {
+        if payload.is_empty() {
+            return;
+        }
+
+        self.split_buffer();
+        self.queued += payload.len();
+        self.queue.push_back(payload);
+    }
+
+    /// Moves encoded bytes of the `buffer` to the queue,
+    /// its allocation is reused once the chunk is
+    /// written
+    fn split_buffer(&mut self) {
+        if !self.buffer.is_empty() {
+            let chunk = self.buffer.split().freeze();
+            self.queued += chunk.len();
+            self.queue.push_back(chunk);
+        }
+    }
+
+    fn advance_queue(&mut self, mut written: usize) {
+        self.queued -= written;
+        while written != 0 {
+            let Some(front) = self.queue.front_mut() else {
+                break;
+            };
+
+            if front.len() > written {
+                front.advance(written);
+                break;
+            }
+            written -= front.len();
+            self.queue.pop_front();
+        }
+    }
+
+    /// Bytes waiting for the write
+    fn buffered(&self) -> usize {
+        self.queued + self.buffer.len()
+    }
+
+    /// Compression attempts made and skipped by the
+    /// adaptive strategy
+    pub const fn compression_stats(&self) -> AdaptiveStats {
+        self.encoder.compression_stats()
+    }
+
+    pub fn into_inner(self) -> (Writer, BufCompressor) {
+        (self.inner, self.encoder.compressor)
+    }
+
     pub fn new(writer: Writer, compressor: BufCompressor) -> Self {
         Self {
             inner: writer,
-            compressor,
+            buffer: BytesMut::new(),
+            queue: VecDeque::new(),
+            queued: 0,
+            encoder: HisuiEncoder::new(compressor),
+            offload: None,
+            coalesce: None,
+            pending_frames: 0,
         }
     }
+
+    /// Compress large buffers on the `pool`
+    pub fn offload_to(&mut self, pool: CompressionPool) {
+        self.offload = Some(pool);
+    }
+
+    /// Keep written frames in the buffer until the `policy`
+    /// limit is reached, the caller must [`Self::flush`]
+    /// them when it has nothing more to write
+    pub fn coalesce(&mut self, policy: FlushPolicy) {
+        self.coalesce = Some(policy);
+    }
+
+    /// Whether frames are held until [`Self::flush`]
+    pub const fn is_coalescing(&self) -> bool {
+        self.coalesce.is_some()
+    }
 }
@@ -1,6 +1,10 @@
+pub mod buffers;
 pub mod hisui;
 pub mod medusa;
 
 pub use common::protocol;
 
 pub mod compression;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,30 @@
+use bytes::BufMut;
+
+use crate::buffers::BufferPool;
+
+#[test]
+fn test_buffer_pool_reuse() {
+    let mut pool = BufferPool::new(1024);
+    let mut pointers = Vec::new();
+    for round in 0..16_u8 {
+        pool.chunk().put_slice(&[round; 512]);
+        let payload = pool.split();
+        assert_eq!(payload.len(), 512);
+        pointers.push(payload.as_ptr());
+    }
+
+    // Dropped payloads give their room back
+    pointers.sort_unstable();
+    pointers.dedup();
+    assert!(pointers.len() <= 8);
+
+    // Alive payloads are never overwritten
+    pool.chunk().put_slice(&[1; 1024]);
+    let first = pool.split();
+    assert_eq!(pool.chunk().remaining_mut(), 1024);
+    for _ in 0..8 {
+        pool.chunk().put_slice(&[2; 1024]);
+        drop(pool.split());
+    }
+    assert!(first.iter().all(|&b| b == 1));
+}